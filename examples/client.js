@@ -99,22 +99,26 @@ function main() {
         try {
             const result = JSON.parse(data.toString());
             
-            if (result.error) {
-                console.log(`❌ Error: ${result.error}`);
-            } else if (result.transcription !== undefined) {
+            if (result.type === 'error') {
+                console.log(`❌ Error: ${result.message}`);
+            } else if (result.type === 'partial') {
+                console.log(`⚡ Partial #${result.seq}: ${result.transcription}`);
+            } else if (result.type === 'transcription' || result.type === 'final') {
                 console.log('='.repeat(60));
-                console.log('✅ Transcription Result:');
+                console.log(`✅ Transcription Result #${result.seq}:`);
                 console.log('='.repeat(60));
                 console.log(`Text: ${result.transcription}`);
-                console.log(`Duration: ${result.duration}s`);
+                console.log(`Time: ${result.start.toFixed(2)}s - ${result.end.toFixed(2)}s (${result.duration}s)`);
                 
-                if (result.segments && result.segments.length > 0) {
+                if (result.segments.length > 0) {
                     console.log('\nSegments:');
                     result.segments.forEach((seg, i) => {
                         console.log(`  ${i + 1}. [${seg.start.toFixed(2)}s - ${seg.end.toFixed(2)}s] ${seg.text}`);
                     });
                 }
                 console.log('='.repeat(60));
+            } else if (result.type === 'no_speech') {
+                console.log(`🔇 No speech detected (${result.start.toFixed(2)}s - ${result.end.toFixed(2)}s)`);
             } else {
                 console.log(`Received: ${data}`);
             }
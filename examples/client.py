@@ -91,20 +91,24 @@ async def receive_results(websocket):
         try:
             result = json.loads(message)
             
-            if "error" in result:
-                print(f"❌ Error: {result['error']}")
-            elif "transcription" in result:
+            if result.get("type") == "error":
+                print(f"❌ Error: {result['message']}")
+            elif result.get("type") == "partial":
+                print(f"⚡ Partial #{result['seq']}: {result['transcription']}")
+            elif result.get("type") in ("transcription", "final"):
                 print("\n" + "="*60)
-                print("✅ Transcription Result:")
+                print(f"✅ Transcription Result #{result['seq']}:")
                 print("="*60)
                 print(f"Text: {result['transcription']}")
-                print(f"Duration: {result.get('duration', 'N/A')}s")
+                print(f"Time: {result['start']:.2f}s - {result['end']:.2f}s ({result['duration']}s)")
                 
-                if "segments" in result and result["segments"]:
+                if result["segments"]:
                     print("\nSegments:")
                     for i, seg in enumerate(result["segments"], 1):
                         print(f"  {i}. [{seg['start']:.2f}s - {seg['end']:.2f}s] {seg['text']}")
                 print("="*60)
+            elif result.get("type") == "no_speech":
+                print(f"🔇 No speech detected ({result['start']:.2f}s - {result['end']:.2f}s)")
             else:
                 print(f"Received: {message}")
         except json.JSONDecodeError:
@@ -0,0 +1,246 @@
+pub mod resample;
+pub mod wav;
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+pub use resample::Resampler;
+pub use wav::decode_wav;
+
+/// Sample rate of everything after decoding: the VAD and Whisper both run at 16 kHz mono
+pub const PIPELINE_SAMPLE_RATE: u32 = 16000;
+
+// Supported range of declared input rates
+pub const MIN_INPUT_SAMPLE_RATE: u32 = 8000;
+pub const MAX_INPUT_SAMPLE_RATE: u32 = 96000;
+pub const MAX_CHANNELS: u16 = 8;
+
+/// Sample encoding of binary audio frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    /// 32-bit float, little-endian
+    F32le,
+    /// 16-bit signed integer, little-endian
+    S16le,
+    /// 8-bit G.711 mu-law
+    Mulaw,
+    /// 8-bit G.711 A-law
+    Alaw,
+}
+
+impl Encoding {
+    pub fn bytes_per_sample(self) -> usize {
+        match self {
+            Encoding::F32le => 4,
+            Encoding::S16le => 2,
+            Encoding::Mulaw | Encoding::Alaw => 1,
+        }
+    }
+
+    fn decode_sample(self, bytes: &[u8]) -> f32 {
+        match self {
+            Encoding::F32le => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            Encoding::S16le => i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+            Encoding::Mulaw => mulaw_to_linear(bytes[0]) as f32 / 32768.0,
+            Encoding::Alaw => alaw_to_linear(bytes[0]) as f32 / 32768.0,
+        }
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "f32le" => Ok(Encoding::F32le),
+            "s16le" => Ok(Encoding::S16le),
+            "mulaw" => Ok(Encoding::Mulaw),
+            "alaw" => Ok(Encoding::Alaw),
+            _ => Err(format!(
+                "Unsupported encoding: {} (expected f32le, s16le, mulaw or alaw)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Encoding::F32le => "f32le",
+            Encoding::S16le => "s16le",
+            Encoding::Mulaw => "mulaw",
+            Encoding::Alaw => "alaw",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Format of the binary frames a client sends
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioFormat {
+    pub encoding: Encoding,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl AudioFormat {
+    /// Bytes of one sample for every channel
+    pub fn frame_size(&self) -> usize {
+        self.encoding.bytes_per_sample() * self.channels as usize
+    }
+}
+
+/// Converts binary frames of the declared format into 16 kHz mono f32
+pub struct AudioDecoder {
+    format: AudioFormat,
+    resampler: Resampler,
+}
+
+impl AudioDecoder {
+    pub fn new(format: AudioFormat) -> Self {
+        Self {
+            format,
+            resampler: Resampler::new(format.sample_rate, PIPELINE_SAMPLE_RATE),
+        }
+    }
+
+    /// Decodes one frame, rejecting it if it does not hold a whole number of samples
+    pub fn decode(&mut self, data: &[u8]) -> Result<Vec<f32>, String> {
+        let frame_size = self.format.frame_size();
+        if !data.len().is_multiple_of(frame_size) {
+            return Err(format!(
+                "Audio frame of {} bytes is not a multiple of {} bytes ({} x {} channel(s))",
+                data.len(),
+                frame_size,
+                self.format.encoding,
+                self.format.channels
+            ));
+        }
+
+        let bytes_per_sample = self.format.encoding.bytes_per_sample();
+        let channels = self.format.channels as f32;
+
+        // Downmix by averaging the channels
+        let mono: Vec<f32> = data
+            .chunks_exact(frame_size)
+            .map(|frame| {
+                frame
+                    .chunks_exact(bytes_per_sample)
+                    .map(|bytes| self.format.encoding.decode_sample(bytes))
+                    .sum::<f32>()
+                    / channels
+            })
+            .collect();
+
+        Ok(self.resampler.process(&mono))
+    }
+
+    /// Returns the converted audio the resampler still holds back
+    pub fn flush(&mut self) -> Vec<f32> {
+        self.resampler.flush()
+    }
+}
+
+/// Decodes a saved recording: a WAV file, or raw frames in `format` as a client sends them
+pub fn decode_recording(data: &[u8], format: AudioFormat) -> Result<Vec<f32>, String> {
+    if data.starts_with(b"RIFF") {
+        return decode_wav(data);
+    }
+
+    let mut decoder = AudioDecoder::new(format);
+    let mut samples = decoder.decode(data)?;
+    samples.extend(decoder.flush());
+    Ok(samples)
+}
+
+/// Decodes a G.711 mu-law byte to 16-bit linear PCM
+fn mulaw_to_linear(byte: u8) -> i16 {
+    let byte = !byte;
+    let exponent = (byte >> 4) & 0x07;
+    let mantissa = (byte & 0x0F) as i32;
+    let magnitude = (((mantissa << 3) + 0x84) << exponent) - 0x84;
+    if byte & 0x80 != 0 {
+        -magnitude as i16
+    } else {
+        magnitude as i16
+    }
+}
+
+/// Decodes a G.711 A-law byte to 16-bit linear PCM
+fn alaw_to_linear(byte: u8) -> i16 {
+    let byte = byte ^ 0x55;
+    let exponent = (byte >> 4) & 0x07;
+    let mantissa = (byte & 0x0F) as i32;
+    let magnitude = if exponent == 0 {
+        (mantissa << 4) + 8
+    } else {
+        ((mantissa << 4) + 0x108) << (exponent - 1)
+    };
+    if byte & 0x80 != 0 {
+        magnitude as i16
+    } else {
+        -magnitude as i16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(encoding: Encoding, sample_rate: u32, channels: u16) -> AudioFormat {
+        AudioFormat {
+            encoding,
+            sample_rate,
+            channels,
+        }
+    }
+
+    #[test]
+    fn g711_reference_values() {
+        assert_eq!(mulaw_to_linear(0xFF), 0);
+        assert_eq!(mulaw_to_linear(0x7F), 0);
+        assert_eq!(mulaw_to_linear(0x80), 32124);
+        assert_eq!(mulaw_to_linear(0x00), -32124);
+
+        assert_eq!(alaw_to_linear(0xD5), 8);
+        assert_eq!(alaw_to_linear(0x55), -8);
+        assert_eq!(alaw_to_linear(0xAA), 32256);
+        assert_eq!(alaw_to_linear(0x2A), -32256);
+    }
+
+    #[test]
+    fn s16le_stereo_is_downmixed() {
+        let mut decoder = AudioDecoder::new(format(Encoding::S16le, 16000, 2));
+        let mut data = Vec::new();
+        for sample in [16384i16, 0, -16384, -16384] {
+            data.extend_from_slice(&sample.to_le_bytes());
+        }
+
+        assert_eq!(decoder.decode(&data).unwrap(), vec![0.25, -0.5]);
+    }
+
+    #[test]
+    fn raw_recordings_use_the_configured_format() {
+        let mut data = Vec::new();
+        for sample in [16384i16, -16384] {
+            data.extend_from_slice(&sample.to_le_bytes());
+        }
+        let samples = decode_recording(&data, format(Encoding::S16le, 16000, 1)).unwrap();
+        assert_eq!(samples, vec![0.5, -0.5]);
+        assert!(decode_recording(&data[..3], format(Encoding::S16le, 16000, 1)).is_err());
+    }
+
+    #[test]
+    fn partial_frames_are_rejected() {
+        let mut decoder = AudioDecoder::new(format(Encoding::F32le, 16000, 1));
+        assert!(decoder.decode(&[0; 6]).is_err());
+
+        let mut decoder = AudioDecoder::new(format(Encoding::S16le, 16000, 2));
+        assert!(decoder.decode(&[0; 6]).is_err());
+        assert_eq!(decoder.decode(&[0; 8]).unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,312 @@
+// Zero crossings of the low-pass sinc kept on each side of the kernel
+const ZERO_CROSSINGS: f64 = 24.0;
+// Cutoff as a fraction of the lower of the two Nyquist frequencies
+const ROLLOFF: f64 = 0.92;
+// Kaiser window shape; about 80 dB of stopband attenuation
+const KAISER_BETA: f64 = 8.0;
+// Ratios needing more phases than this use the nearest of this many phases
+const MAX_PHASES: usize = 512;
+
+/// Streaming band-limited resampler (polyphase windowed sinc).
+///
+/// Output sample `n` is aligned with input time `n * input_rate / output_rate`.
+/// Output lags the input by half the kernel; `flush` returns the rest.
+pub struct Resampler {
+    input_rate: u32,
+    output_rate: u32,
+    // `up` output samples for every `down` input samples
+    up: usize,
+    down: usize,
+    phases: usize,
+    // Kernel half length, in input samples
+    half: usize,
+    // One row of `2 * half` coefficients per phase
+    filter: Vec<f32>,
+    // Input samples still needed by the kernel
+    history: Vec<f32>,
+    // History index of the input sample at or before the next output time
+    index: usize,
+    // Fractional part of the next output time, in units of 1/up
+    frac: usize,
+    consumed: u64,
+    produced: u64,
+}
+
+impl Resampler {
+    pub fn new(input_rate: u32, output_rate: u32) -> Self {
+        let divisor = gcd(input_rate as usize, output_rate as usize);
+        let up = output_rate as usize / divisor;
+        let down = input_rate as usize / divisor;
+        let phases = up.min(MAX_PHASES);
+
+        // Low-pass below the lower Nyquist frequency, in cycles per input sample
+        let scale = (output_rate as f64 / input_rate as f64).min(1.0);
+        let cutoff = 0.5 * scale * ROLLOFF;
+        let half = (ZERO_CROSSINGS / (2.0 * cutoff)).ceil() as usize;
+        let taps = 2 * half;
+
+        let mut filter = Vec::with_capacity(phases * taps);
+        for phase in 0..phases {
+            let offset = phase as f64 / phases as f64;
+            let row: Vec<f64> = (0..taps)
+                .map(|j| {
+                    let t = offset + half as f64 - 1.0 - j as f64;
+                    2.0 * cutoff * sinc(2.0 * cutoff * t) * kaiser(t / half as f64)
+                })
+                .collect();
+
+            // Unity gain at DC for every phase
+            let sum: f64 = row.iter().sum();
+            filter.extend(row.iter().map(|c| (c / sum) as f32));
+        }
+
+        Self {
+            input_rate,
+            output_rate,
+            up,
+            down,
+            phases,
+            half,
+            filter,
+            history: vec![0.0; half - 1],
+            index: half - 1,
+            frac: 0,
+            consumed: 0,
+            produced: 0,
+        }
+    }
+
+    fn is_passthrough(&self) -> bool {
+        self.up == self.down
+    }
+
+    pub fn process(&mut self, input: &[f32]) -> Vec<f32> {
+        if self.is_passthrough() {
+            return input.to_vec();
+        }
+
+        self.history.extend_from_slice(input);
+        self.consumed += input.len() as u64;
+
+        let mut output = Vec::with_capacity(input.len() * self.up / self.down + 1);
+        self.drain(&mut output);
+        output
+    }
+
+    /// Returns the samples still held back by the kernel and starts over.
+    /// The output then covers exactly the input received so far.
+    pub fn flush(&mut self) -> Vec<f32> {
+        if self.is_passthrough() {
+            return Vec::new();
+        }
+
+        let total = (self.consumed * self.up as u64).div_ceil(self.down as u64);
+        let remaining = total.saturating_sub(self.produced) as usize;
+
+        self.history.extend(std::iter::repeat_n(0.0, self.half + 1));
+        let mut output = Vec::with_capacity(remaining);
+        self.drain(&mut output);
+        output.truncate(remaining);
+
+        *self = Self::new(self.input_rate, self.output_rate);
+        output
+    }
+
+    fn drain(&mut self, output: &mut Vec<f32>) {
+        let taps = 2 * self.half;
+
+        loop {
+            let (center, phase) = self.position();
+            if center + self.half >= self.history.len() {
+                break;
+            }
+
+            let row = &self.filter[phase * taps..(phase + 1) * taps];
+            let window = &self.history[center + 1 - self.half..=center + self.half];
+            output.push(row.iter().zip(window).map(|(c, x)| c * x).sum());
+            self.produced += 1;
+
+            self.frac += self.down;
+            self.index += self.frac / self.up;
+            self.frac %= self.up;
+        }
+
+        // Drop input that is behind the kernel for good
+        let used = self.index + 1 - self.half;
+        self.history.drain(..used);
+        self.index -= used;
+    }
+
+    /// Input sample and filter phase of the next output sample
+    fn position(&self) -> (usize, usize) {
+        if self.phases == self.up {
+            return (self.index, self.frac);
+        }
+
+        // Nearest quantized phase
+        let phase = (2 * self.frac * self.phases + self.up) / (2 * self.up);
+        if phase == self.phases {
+            (self.index + 1, 0)
+        } else {
+            (self.index, phase)
+        }
+    }
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-12 {
+        1.0
+    } else {
+        let x = std::f64::consts::PI * x;
+        x.sin() / x
+    }
+}
+
+fn kaiser(x: f64) -> f64 {
+    if x.abs() > 1.0 {
+        return 0.0;
+    }
+    bessel_i0(KAISER_BETA * (1.0 - x * x).sqrt()) / bessel_i0(KAISER_BETA)
+}
+
+/// Modified Bessel function of the first kind, order 0
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half = x / 2.0;
+    for k in 1..50 {
+        term *= (half / k as f64) * (half / k as f64);
+        sum += term;
+        if term < sum * 1e-12 {
+            break;
+        }
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64::consts::PI;
+
+    const OUTPUT_RATE: u32 = 16000;
+
+    fn tone(rate: u32, frequency: f64, seconds: f64) -> Vec<f32> {
+        let count = (rate as f64 * seconds) as usize;
+        (0..count)
+            .map(|n| (2.0 * PI * frequency * n as f64 / rate as f64).sin() as f32)
+            .collect()
+    }
+
+    fn resample_in_chunks(input_rate: u32, input: &[f32], chunk: usize) -> Vec<f32> {
+        let mut resampler = Resampler::new(input_rate, OUTPUT_RATE);
+        let mut output = Vec::new();
+        for part in input.chunks(chunk) {
+            output.extend(resampler.process(part));
+        }
+        output.extend(resampler.flush());
+        output
+    }
+
+    // Skips the edges, where the kernel overlaps the implicit silence around the input
+    fn steady_part(output: &[f32]) -> &[f32] {
+        &output[400..output.len() - 400]
+    }
+
+    #[test]
+    fn passband_tone_keeps_amplitude_and_phase() {
+        for rate in [8000, 11025, 22050, 32000, 44100, 48000, 96000] {
+            let output = resample_in_chunks(rate, &tone(rate, 1000.0, 1.0), 1000);
+            let expected = tone(OUTPUT_RATE, 1000.0, 1.0);
+
+            let error = steady_part(&output)
+                .iter()
+                .zip(steady_part(&expected))
+                .map(|(a, b)| (a - b).abs())
+                .fold(0.0f32, f32::max);
+            assert!(error < 0.01, "{} Hz: max error {}", rate, error);
+        }
+    }
+
+    #[test]
+    fn tones_above_output_nyquist_are_removed() {
+        for (rate, frequency) in [
+            (22050, 9000.0),
+            (44100, 9000.0),
+            (48000, 12000.0),
+            (96000, 20000.0),
+        ] {
+            let output = resample_in_chunks(rate, &tone(rate, frequency, 1.0), 1000);
+
+            let steady = steady_part(&output);
+            let rms = (steady.iter().map(|s| s * s).sum::<f32>() / steady.len() as f32).sqrt();
+            // A full-scale sine has an RMS of 0.707; require at least 60 dB less
+            assert!(
+                rms < 0.707e-3,
+                "{} Hz input, {} Hz tone: rms {}",
+                rate,
+                frequency,
+                rms
+            );
+        }
+    }
+
+    #[test]
+    fn impulse_lands_on_the_matching_output_sample() {
+        for rate in [8000, 44100, 48000] {
+            let mut input = vec![0.0f32; rate as usize];
+            // 0.25 s into the input
+            input[rate as usize / 4] = 1.0;
+
+            let output = resample_in_chunks(rate, &input, 777);
+            let peak = output
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.total_cmp(b.1))
+                .map(|(i, _)| i)
+                .unwrap();
+            assert_eq!(peak, OUTPUT_RATE as usize / 4, "{} Hz", rate);
+        }
+    }
+
+    #[test]
+    fn output_length_matches_the_input_duration() {
+        for rate in [8000, 11025, 44100, 48000, 96000, 44101] {
+            let output = resample_in_chunks(rate, &vec![0.0; rate as usize], 1234);
+            assert_eq!(output.len(), OUTPUT_RATE as usize, "{} Hz", rate);
+        }
+    }
+
+    #[test]
+    fn chunk_size_does_not_change_the_output() {
+        let mut seed = 1u32;
+        let input: Vec<f32> = (0..20000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                (seed >> 8) as f32 / (1 << 24) as f32 - 0.5
+            })
+            .collect();
+
+        let whole = resample_in_chunks(44100, &input, input.len());
+        for chunk in [1, 7, 441, 4096] {
+            assert_eq!(
+                resample_in_chunks(44100, &input, chunk),
+                whole,
+                "chunk {}",
+                chunk
+            );
+        }
+    }
+
+    #[test]
+    fn pipeline_rate_passes_through() {
+        let input = tone(OUTPUT_RATE, 440.0, 0.1);
+        let mut resampler = Resampler::new(OUTPUT_RATE, OUTPUT_RATE);
+        assert_eq!(resampler.process(&input), input);
+        assert!(resampler.flush().is_empty());
+    }
+}
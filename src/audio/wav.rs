@@ -0,0 +1,49 @@
+use std::io::Cursor;
+
+use hound::{SampleFormat, WavReader};
+
+use super::{MAX_INPUT_SAMPLE_RATE, MIN_INPUT_SAMPLE_RATE, PIPELINE_SAMPLE_RATE, Resampler};
+
+/// Decodes a WAV file into 16 kHz mono f32
+pub fn decode_wav(data: &[u8]) -> Result<Vec<f32>, String> {
+    let mut reader =
+        WavReader::new(Cursor::new(data)).map_err(|e| format!("Invalid WAV file: {}", e))?;
+    let spec = reader.spec();
+
+    if !(MIN_INPUT_SAMPLE_RATE..=MAX_INPUT_SAMPLE_RATE).contains(&spec.sample_rate) {
+        return Err(format!(
+            "WAV sample rate must be between {} and {} Hz (got {})",
+            MIN_INPUT_SAMPLE_RATE, MAX_INPUT_SAMPLE_RATE, spec.sample_rate
+        ));
+    }
+
+    let samples: Vec<f32> = match (spec.sample_format, spec.bits_per_sample) {
+        (SampleFormat::Float, 32) => reader.samples::<f32>().collect::<Result<_, _>>(),
+        (SampleFormat::Int, bits @ 8..=32) => {
+            let scale = (1i64 << (bits - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|value| value as f32 / scale))
+                .collect::<Result<_, _>>()
+        }
+        (format, bits) => {
+            return Err(format!(
+                "Unsupported WAV sample format: {:?} {} bit",
+                format, bits
+            ));
+        }
+    }
+    .map_err(|e| format!("Invalid WAV data: {}", e))?;
+
+    // Downmix by averaging the channels
+    let channels = spec.channels.max(1) as usize;
+    let mono: Vec<f32> = samples
+        .chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+
+    let mut resampler = Resampler::new(spec.sample_rate, PIPELINE_SAMPLE_RATE);
+    let mut output = resampler.process(&mono);
+    output.extend(resampler.flush());
+    Ok(output)
+}
@@ -0,0 +1,158 @@
+use std::collections::{HashMap, VecDeque};
+
+use serde::Serialize;
+use tokio::sync::oneshot;
+
+use crate::audio::PIPELINE_SAMPLE_RATE;
+use crate::config::Config;
+use crate::protocol::{SegmentInfo, ServerMessage};
+use crate::transcriber::CancelToken;
+use crate::vad::{SpeechSegment, VadEvent, VadFactory};
+use crate::worker::{DecodeOptions, Task, TaskKind, WorkerPool};
+
+/// Result of transcribing a whole recording
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchTranscription {
+    pub text: String,
+    pub segments: Vec<SegmentInfo>,
+    // Length of the recording in seconds
+    pub duration: f64,
+    // Detected language covering the most speech, in `auto` mode
+    pub language: Option<String>,
+}
+
+/// Cancels the queued utterances when the request is dropped before finishing
+struct CancelOnDrop(CancelToken);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+/// Runs a complete 16 kHz recording through the same VAD and worker pool as live sessions.
+/// At most `max_in_flight` utterances are queued at once, so a long file does not
+/// crowd out live sessions.
+pub async fn transcribe_recording(
+    pool: &WorkerPool,
+    vad: &VadFactory,
+    config: &Config,
+    samples: Vec<f32>,
+    language: String,
+    options: DecodeOptions,
+) -> Result<BatchTranscription, String> {
+    let duration = samples.len() as f64 / PIPELINE_SAMPLE_RATE as f64;
+
+    // The VAD runs ONNX inference over the whole file, so keep it off the async runtime
+    let (vad, vad_config) = (vad.clone(), config.clone());
+    let (samples, speech) = tokio::task::spawn_blocking(move || {
+        let speech = detect_speech(&vad, &vad_config, &samples);
+        (samples, speech)
+    })
+    .await
+    .map_err(|e| format!("VAD task failed: {}", e))?;
+    let speech = speech?;
+
+    println!(
+        "📂 Transcribing recording ({:.2}s, {} utterance(s))",
+        duration,
+        speech.len()
+    );
+
+    let guard = CancelOnDrop(CancelToken::default());
+    let max_in_flight = config.max_in_flight.max(1);
+    let mut pending: VecDeque<oneshot::Receiver<ServerMessage>> = VecDeque::new();
+    let mut results = Vec::new();
+
+    for (seq, segment) in speech.iter().enumerate() {
+        let start = segment.start_offset.min(samples.len());
+        let end = segment.end_offset.min(samples.len());
+        if end - start < config.min_speech_samples {
+            continue;
+        }
+
+        if pending.len() >= max_in_flight
+            && let Some(result) = pending.pop_front()
+        {
+            results.push(result.await);
+        }
+
+        let (result_tx, result_rx) = oneshot::channel();
+        pool.submit(Task {
+            seq: seq as u64,
+            kind: TaskKind::Transcription,
+            audio_data: samples[start..end].to_vec(),
+            language: language.clone(),
+            start_time: start as f64 / PIPELINE_SAMPLE_RATE as f64,
+            options: options.clone(),
+            responder: result_tx,
+            cancel: guard.0.clone(),
+        });
+        pending.push_back(result_rx);
+    }
+
+    while let Some(result) = pending.pop_front() {
+        results.push(result.await);
+    }
+
+    let mut transcription = BatchTranscription {
+        text: String::new(),
+        segments: Vec::new(),
+        duration,
+        language: None,
+    };
+    // Seconds of speech per detected language
+    let mut languages: HashMap<String, f64> = HashMap::new();
+    for result in results {
+        match result {
+            Ok(ServerMessage::Transcription {
+                segments,
+                detected_language,
+                duration,
+                ..
+            }) => {
+                if let Some(detected) = detected_language {
+                    *languages.entry(detected.language).or_default() += duration;
+                }
+                transcription.segments.extend(segments);
+            }
+            Ok(ServerMessage::Error { message, .. }) => return Err(message),
+            Ok(_) => {}
+            Err(_) => return Err("Transcription was cancelled".to_string()),
+        }
+    }
+
+    transcription.language = languages
+        .into_iter()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(language, _)| language);
+    transcription.text = transcription
+        .segments
+        .iter()
+        .map(|segment| segment.text.as_str())
+        .collect::<Vec<_>>()
+        .join(" ");
+    Ok(transcription)
+}
+
+/// Speech segments of a complete 16 kHz recording, as the streaming VAD of a session finds them
+pub fn detect_speech(
+    vad: &VadFactory,
+    config: &Config,
+    samples: &[f32],
+) -> Result<Vec<SpeechSegment>, String> {
+    let mut vad = vad
+        .create(config)
+        .map_err(|e| format!("VAD initialization failed: {}", e))?;
+
+    let mut events = vad.process_chunk(samples).map_err(|e| e.to_string())?;
+    events.extend(vad.finish().map_err(|e| e.to_string())?);
+
+    Ok(events
+        .into_iter()
+        .filter_map(|event| match event {
+            VadEvent::SpeechEnd(segment) => Some(segment),
+            VadEvent::SpeechStart { .. } => None,
+        })
+        .collect())
+}
@@ -0,0 +1,339 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use whisper_server_ws::audio::{PIPELINE_SAMPLE_RATE, decode_recording};
+use whisper_server_ws::batch::{BatchTranscription, detect_speech, transcribe_recording};
+use whisper_server_ws::config::{Config, Settings};
+use whisper_server_ws::context::SessionContext;
+use whisper_server_ws::filter::SegmentFilter;
+use whisper_server_ws::server;
+use whisper_server_ws::subtitle::{to_srt, to_vtt};
+use whisper_server_ws::transcriber::load_model;
+use whisper_server_ws::vad::{SpeechSegment, load_vad};
+use whisper_server_ws::worker::{DecodeOptions, WorkerPool, load_dictionaries};
+
+/// Real-time speech recognition server for WebSocket audio streams
+#[derive(Debug, Parser)]
+#[command(version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// TOML config file (default: $CONFIG_FILE)
+    #[arg(short, long, value_name = "PATH", global = true)]
+    pub config: Option<String>,
+
+    /// Address to listen on
+    #[arg(long, global = true)]
+    pub host: Option<String>,
+
+    /// WebSocket port
+    #[arg(long, global = true)]
+    pub port: Option<String>,
+
+    /// HTTP API port; 0 disables it
+    #[arg(long, global = true)]
+    pub http_port: Option<String>,
+
+    /// Whisper model file
+    #[arg(long, value_name = "PATH", global = true)]
+    pub model: Option<String>,
+
+    /// VAD backend: silero or energy
+    #[arg(long, value_name = "BACKEND", global = true)]
+    pub vad: Option<String>,
+
+    /// Silero VAD model file
+    #[arg(long, value_name = "PATH", global = true)]
+    pub vad_model: Option<String>,
+
+    /// Language code, or "auto"
+    #[arg(long, global = true)]
+    pub language: Option<String>,
+
+    /// Any other setting, e.g. --set vad_threshold=0.6 (repeatable)
+    #[arg(long = "set", value_name = "KEY=VALUE", global = true)]
+    pub set: Vec<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Starts the WebSocket server and the HTTP API (the default)
+    Serve,
+    /// Transcribes a recording with the same VAD and Whisper pipeline as live sessions
+    Transcribe(TranscribeArgs),
+    /// Prints the speech segments the VAD finds in a recording
+    Vad(VadArgs),
+    /// Loads the configuration, models, NG rules and dictionaries, then exits
+    CheckConfig,
+}
+
+#[derive(Debug, Args)]
+pub struct TranscribeArgs {
+    /// WAV file, or raw audio in AUDIO_ENCODING, SAMPLE_RATE and AUDIO_CHANNELS
+    pub file: PathBuf,
+
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// Output file, or "-" for stdout (default: the input path with the format's extension)
+    #[arg(short, long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct VadArgs {
+    /// WAV file, or raw audio in AUDIO_ENCODING, SAMPLE_RATE and AUDIO_CHANNELS
+    pub file: PathBuf,
+
+    /// Prints the segments as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Json,
+    Srt,
+    Vtt,
+    Text,
+}
+
+impl OutputFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Json => "json",
+            OutputFormat::Srt => "srt",
+            OutputFormat::Vtt => "vtt",
+            OutputFormat::Text => "txt",
+        }
+    }
+}
+
+impl Cli {
+    /// Defaults, then the config file, environment variables and flags, each
+    /// replacing the one before. Fails on the first invalid setting or missing file.
+    pub fn load_config(&self) -> Result<Config, String> {
+        dotenv::dotenv().ok();
+
+        let mut settings = Settings::default();
+        let file = self
+            .config
+            .clone()
+            .or_else(|| env::var("CONFIG_FILE").ok().filter(|path| !path.is_empty()));
+        if let Some(path) = &file {
+            settings.load_file(path)?;
+        }
+
+        settings.load_env(|key| env::var(key).ok());
+
+        let flags = [
+            ("host", "--host", &self.host),
+            ("port", "--port", &self.port),
+            ("http_port", "--http-port", &self.http_port),
+            ("whisper_model_path", "--model", &self.model),
+            ("vad_backend", "--vad", &self.vad),
+            ("vad_model_path", "--vad-model", &self.vad_model),
+            ("whisper_language", "--language", &self.language),
+        ];
+        for (key, flag, value) in flags {
+            if let Some(value) = value {
+                settings.set(key, value, flag)?;
+            }
+        }
+        for pair in &self.set {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid --set {}: expected KEY=VALUE", pair))?;
+            settings.set(key, value, "--set")?;
+        }
+
+        let config = Config::from_settings(&settings)?;
+        config.check_files()?;
+        Ok(config)
+    }
+
+    /// Runs the subcommand, serving when none is given
+    pub async fn run(&self, config: Config) -> Result<(), String> {
+        match &self.command {
+            None | Some(Command::Serve) => {
+                config.print_config();
+                server::serve(config).await
+            }
+            Some(Command::Transcribe(args)) => transcribe(config, args).await,
+            Some(Command::Vad(args)) => vad(&config, args),
+            Some(Command::CheckConfig) => check_config(&config),
+        }
+    }
+}
+
+async fn transcribe(config: Config, args: &TranscribeArgs) -> Result<(), String> {
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| args.file.with_extension(args.format.extension()));
+    if output == args.file {
+        return Err(format!(
+            "Output {} would overwrite the input; choose another with --output",
+            output.display()
+        ));
+    }
+
+    let samples = read_recording(&args.file, &config)?;
+
+    let ctx = Arc::new(load_model(&config)?);
+    let pool = WorkerPool::whisper(ctx, &config, Arc::new(AtomicBool::new(false)))?;
+    let vad = load_vad(&config)?;
+    let options = DecodeOptions {
+        word_timestamps: config.word_timestamps,
+        language_candidates: config.language_candidates.clone(),
+        task: config.whisper_task,
+        include_source: config.include_source,
+        prompt: config.whisper_prompt.clone(),
+        context_tokens: config.context_tokens,
+        context: Some(Arc::new(SessionContext::default())),
+        dictionary: config.dictionary.clone(),
+        include_raw: config.include_raw,
+        ..DecodeOptions::default()
+    };
+    let language = config.whisper_language.clone();
+    let transcription =
+        transcribe_recording(&pool, &vad, &config, samples, language, options).await?;
+
+    let content = render(&transcription, &config, args.format)?;
+    if output == Path::new("-") {
+        print!("{}", content);
+    } else {
+        fs::write(&output, content)
+            .map_err(|e| format!("Failed to write {}: {}", output.display(), e))?;
+        println!("💾 Wrote {}", output.display());
+    }
+    Ok(())
+}
+
+fn render(
+    transcription: &BatchTranscription,
+    config: &Config,
+    format: OutputFormat,
+) -> Result<String, String> {
+    Ok(match format {
+        OutputFormat::Json => {
+            let mut json = serde_json::to_string_pretty(transcription)
+                .map_err(|e| format!("Failed to serialize the transcription: {}", e))?;
+            json.push('\n');
+            json
+        }
+        OutputFormat::Srt => to_srt(&transcription.segments, &config.subtitle_options()),
+        OutputFormat::Vtt => to_vtt(&transcription.segments, &config.subtitle_options()),
+        OutputFormat::Text => format!("{}\n", transcription.text),
+    })
+}
+
+fn vad(config: &Config, args: &VadArgs) -> Result<(), String> {
+    let samples = read_recording(&args.file, config)?;
+    let vad = load_vad(config)?;
+    let segments = detect_speech(&vad, config, &samples)?;
+
+    if args.json {
+        let json = serde_json::to_string_pretty(&segments)
+            .map_err(|e| format!("Failed to serialize the segments: {}", e))?;
+        println!("{}", json);
+        return Ok(());
+    }
+
+    println!(
+        "{:>4} {:>10} {:>10} {:>10}",
+        "#", "start", "end", "duration"
+    );
+    for (index, segment) in segments.iter().enumerate() {
+        println!(
+            "{:>4} {:>10.3} {:>10.3} {:>10.3}",
+            index + 1,
+            segment.start_second,
+            segment.end_second,
+            segment.end_second - segment.start_second
+        );
+    }
+    println!(
+        "{} segment(s), {:.3}s of speech in {:.3}s",
+        segments.len(),
+        speech_seconds(&segments),
+        samples.len() as f64 / PIPELINE_SAMPLE_RATE as f64
+    );
+    Ok(())
+}
+
+fn speech_seconds(segments: &[SpeechSegment]) -> f64 {
+    segments
+        .iter()
+        .map(|segment| (segment.end_offset - segment.start_offset) as f64)
+        .sum::<f64>()
+        / PIPELINE_SAMPLE_RATE as f64
+}
+
+/// Loads everything `serve` would, without starting workers or listeners
+fn check_config(config: &Config) -> Result<(), String> {
+    config.print_config();
+
+    load_model(config)?;
+    load_vad(config)?
+        .create(config)
+        .map_err(|e| format!("Failed to create VAD: {}", e))?;
+    println!("✅ VAD ready ({})", config.vad_backend);
+    SegmentFilter::from_config(config)?;
+    load_dictionaries(config)?;
+
+    println!("✅ Configuration OK");
+    Ok(())
+}
+
+/// Reads a recording as 16 kHz mono
+fn read_recording(path: &Path, config: &Config) -> Result<Vec<f32>, String> {
+    let data = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    decode_recording(&data, config.audio_format())
+        .map_err(|e| format!("Failed to decode {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn arguments_are_consistent() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn config_flags_are_accepted_after_the_subcommand() {
+        let cli = Cli::try_parse_from([
+            "whisper-server-ws",
+            "transcribe",
+            "meeting.wav",
+            "--format",
+            "srt",
+            "--set",
+            "vad_threshold=0.6",
+        ])
+        .unwrap();
+        assert_eq!(cli.set, vec!["vad_threshold=0.6"]);
+        match cli.command {
+            Some(Command::Transcribe(args)) => {
+                assert_eq!(args.format, OutputFormat::Srt);
+                assert_eq!(
+                    args.file.with_extension(args.format.extension()),
+                    Path::new("meeting.srt")
+                );
+            }
+            command => panic!("unexpected command: {:?}", command),
+        }
+
+        let cli = Cli::try_parse_from(["whisper-server-ws"]).unwrap();
+        assert!(cli.command.is_none());
+    }
+}
@@ -1,18 +1,96 @@
-use std::env;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::audio::{
+    AudioFormat, Encoding, MAX_CHANNELS, MAX_INPUT_SAMPLE_RATE, MIN_INPUT_SAMPLE_RATE,
+    PIPELINE_SAMPLE_RATE,
+};
+use crate::filter::FilterAction;
+use crate::session::{
+    MAX_BLOCK_SECONDS, MAX_CONTEXT_TOKENS, MAX_DURATION_MS, MAX_SPEECH_PAD_MS, MAX_SUBTITLE_LINES,
+    MIN_PARTIAL_INTERVAL_MS,
+};
+use crate::subtitle::SubtitleOptions;
+use crate::vad::VadBackend;
+use crate::worker::{AUTO_LANGUAGE, WhisperTask, is_supported_language};
+
+/// Every setting. Config file keys are these names; environment variables are the same
+/// names in upper case.
+pub const KEYS: &[&str] = &[
+    "host",
+    "port",
+    "http_port",
+    "whisper_model_path",
+    "whisper_language",
+    "whisper_language_candidates",
+    "whisper_task",
+    "translate_include_source",
+    "whisper_prompt",
+    "context_tokens",
+    "whisper_threads",
+    "whisper_workers",
+    "whisper_block_seconds",
+    "vad_backend",
+    "vad_model_path",
+    "vad_threshold",
+    "vad_min_speech_duration_ms",
+    "vad_max_speech_duration_seconds",
+    "vad_min_silence_duration_ms",
+    "vad_speech_pad_ms",
+    "sample_rate",
+    "audio_encoding",
+    "audio_channels",
+    "min_speech_samples",
+    "max_in_flight",
+    "transcribe_on_disconnect",
+    "partial_interval_ms",
+    "word_timestamps",
+    "subtitle_max_line_width",
+    "subtitle_max_lines",
+    "subtitle_max_cue_seconds",
+    "ng_words",
+    "ng_rules_path",
+    "dictionary_dir",
+    "dictionary",
+    "dictionary_include_raw",
+    "no_speech_threshold",
+    "logprob_threshold",
+    "compression_ratio_threshold",
+    "repetition_threshold",
+    "hallucination_action",
+];
 
 #[derive(Debug, Clone)]
 pub struct Config {
     // Server settings
     pub host: String,
     pub port: u16,
+    // 0 disables the HTTP API
+    pub http_port: u16,
 
     // Whisper settings
     pub whisper_model_path: String,
+    // Language code, or "auto" to detect it per utterance
     pub whisper_language: String,
+    // Languages "auto" may choose from; empty allows all
+    pub language_candidates: Vec<String>,
+    pub whisper_task: WhisperTask,
+    // Static initial prompt, e.g. names and terms
+    pub whisper_prompt: Option<String>,
+    // Tokens of recent session text used as the prompt; 0 disables
+    pub context_tokens: usize,
+    // In translate mode, also send the source-language transcript
+    pub include_source: bool,
     pub whisper_threads: usize,
+    pub whisper_workers: usize,
     pub whisper_block_seconds: usize,
 
     // VAD settings
+    pub vad_backend: VadBackend,
+    // Only used by the Silero backend
     pub vad_model_path: String,
     pub vad_threshold: f32,
     pub vad_min_speech_duration_ms: i32,
@@ -20,116 +98,374 @@ pub struct Config {
     pub vad_min_silence_duration_ms: i32,
     pub vad_speech_pad_ms: i32,
 
+    // Input audio format (defaults for sessions)
+    pub sample_rate: u32,
+    pub encoding: Encoding,
+    pub channels: u16,
+
     // Processing settings
-    pub sample_rate: i32,
     pub min_speech_samples: usize,
-    pub max_silence_samples: usize,
-    pub max_speech_samples: usize,
+    pub max_in_flight: usize,
+    pub transcribe_on_disconnect: bool,
+    // 0 disables partial results
+    pub partial_interval_ms: u64,
+    pub word_timestamps: bool,
+
+    // Subtitle export settings
+    pub subtitle_max_line_width: usize,
+    pub subtitle_max_lines: usize,
+    pub subtitle_max_cue_seconds: f64,
 
     // NG words (words to filter out)
     pub ng_words: Vec<String>,
+    // TOML file of NG rules, applied after `ng_words`
+    pub ng_rules_path: Option<String>,
+
+    // Replacement dictionaries
+    pub dictionary_dir: Option<String>,
+    // Dictionary used unless a session picks another
+    pub dictionary: Option<String>,
+    // Adds Whisper's text next to the corrected text
+    pub include_raw: bool,
+
+    // Hallucination filters; None disables a check
+    pub no_speech_threshold: Option<f32>,
+    pub logprob_threshold: Option<f32>,
+    pub compression_ratio_threshold: Option<f32>,
+    pub repetition_threshold: Option<usize>,
+    pub filter_action: FilterAction,
+}
+
+impl Default for Config {
+    /// The built-in defaults, as used when nothing is configured
+    fn default() -> Self {
+        Self::from_settings(&Settings::default()).expect("the defaults are valid")
+    }
 }
 
 impl Config {
-    pub fn from_env() -> Self {
-        dotenv::dotenv().ok();
+    /// Parses and checks the settings; anything not given keeps its default
+    pub fn from_settings(settings: &Settings) -> Result<Self, String> {
+        let config = Self {
+            host: settings.value("host", "127.0.0.1".to_string())?,
+            port: settings.value("port", 9000)?,
+            http_port: settings.value("http_port", 9001)?,
 
-        let host = env::var("HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
-        let port = env::var("PORT")
-            .unwrap_or_else(|_| "9000".to_string())
-            .parse()
-            .unwrap_or(9000);
+            whisper_model_path: settings
+                .value("whisper_model_path", "./models/ggml-base.bin".to_string())?,
+            whisper_language: settings.value("whisper_language", "ja".to_string())?,
+            language_candidates: settings.list("whisper_language_candidates", &[]),
+            whisper_task: settings.value("whisper_task", WhisperTask::Transcribe)?,
+            whisper_prompt: settings.optional("whisper_prompt")?,
+            context_tokens: settings.value("context_tokens", 0)?,
+            include_source: settings.value("translate_include_source", false)?,
+            whisper_threads: settings.value("whisper_threads", num_cpus::get())?,
+            whisper_workers: settings.value("whisper_workers", 1)?,
+            whisper_block_seconds: settings.value("whisper_block_seconds", 30)?,
 
-        let whisper_model_path =
-            env::var("WHISPER_MODEL_PATH").unwrap_or_else(|_| "./models/ggml-base.bin".to_string());
-        let whisper_language = env::var("WHISPER_LANGUAGE").unwrap_or_else(|_| "ja".to_string());
-        let whisper_threads = env::var("WHISPER_THREADS")
-            .unwrap_or_else(|_| num_cpus::get().to_string())
-            .parse()
-            .unwrap_or_else(|_| num_cpus::get());
-        let whisper_block_seconds = env::var("WHISPER_BLOCK_SECONDS")
-            .unwrap_or_else(|_| "30".to_string())
-            .parse()
-            .unwrap_or(30);
+            vad_backend: settings.value("vad_backend", VadBackend::Silero)?,
+            vad_model_path: settings
+                .value("vad_model_path", "./models/silero_vad.onnx".to_string())?,
+            vad_threshold: settings.value("vad_threshold", 0.5)?,
+            vad_min_speech_duration_ms: settings.value("vad_min_speech_duration_ms", 250)?,
+            vad_max_speech_duration_seconds: settings
+                .value("vad_max_speech_duration_seconds", f32::INFINITY)?,
+            vad_min_silence_duration_ms: settings.value("vad_min_silence_duration_ms", 100)?,
+            vad_speech_pad_ms: settings.value("vad_speech_pad_ms", 30)?,
 
-        let vad_model_path =
-            env::var("VAD_MODEL_PATH").unwrap_or_else(|_| "./models/silero_vad.onnx".to_string());
-        let vad_threshold = env::var("VAD_THRESHOLD")
-            .unwrap_or_else(|_| "0.5".to_string())
-            .parse()
-            .unwrap_or(0.5);
-        let vad_min_speech_duration_ms = env::var("VAD_MIN_SPEECH_DURATION_MS")
-            .unwrap_or_else(|_| "250".to_string())
-            .parse()
-            .unwrap_or(250);
-        let vad_max_speech_duration_seconds = env::var("VAD_MAX_SPEECH_DURATION_SECONDS")
-            .unwrap_or_else(|_| "inf".to_string())
-            .parse()
-            .unwrap_or(f32::INFINITY);
-        let vad_min_silence_duration_ms = env::var("VAD_MIN_SILENCE_DURATION_MS")
-            .unwrap_or_else(|_| "100".to_string())
-            .parse()
-            .unwrap_or(100);
-        let vad_speech_pad_ms = env::var("VAD_SPEECH_PAD_MS")
-            .unwrap_or_else(|_| "30".to_string())
-            .parse()
-            .unwrap_or(30);
+            sample_rate: settings.value("sample_rate", 16000)?,
+            encoding: settings.value("audio_encoding", Encoding::F32le)?,
+            channels: settings.value("audio_channels", 1)?,
 
-        let sample_rate = env::var("SAMPLE_RATE")
-            .unwrap_or_else(|_| "16000".to_string())
-            .parse()
-            .unwrap_or(16000);
+            // 0.5 seconds at 16kHz
+            min_speech_samples: settings.value("min_speech_samples", 8000)?,
+            max_in_flight: settings.value("max_in_flight", 4)?,
+            transcribe_on_disconnect: settings.value("transcribe_on_disconnect", false)?,
+            partial_interval_ms: settings.value("partial_interval_ms", 0)?,
+            word_timestamps: settings.value("word_timestamps", false)?,
 
-        let min_speech_samples = env::var("MIN_SPEECH_SAMPLES")
-            .unwrap_or_else(|_| "8000".to_string()) // 0.5 seconds at 16kHz
-            .parse()
-            .unwrap_or(8000);
-        let max_silence_samples = env::var("MAX_SILENCE_SAMPLES")
-            .unwrap_or_else(|_| "16000".to_string()) // 1 second at 16kHz
-            .parse()
-            .unwrap_or(16000);
-        let max_speech_samples = env::var("MAX_SPEECH_SAMPLES")
-            .unwrap_or_else(|_| "48000".to_string()) // 3 seconds at 16kHz
-            .parse()
-            .unwrap_or(48000);
-
-        let ng_words_str = env::var("NG_WORDS")
-            .unwrap_or_else(|_| "あ,ん,ご視聴ありがとうございました".to_string());
-        let ng_words = ng_words_str
-            .split(',')
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty())
-            .collect();
+            subtitle_max_line_width: settings.value("subtitle_max_line_width", 42)?,
+            subtitle_max_lines: settings.value("subtitle_max_lines", 2)?,
+            subtitle_max_cue_seconds: settings.value("subtitle_max_cue_seconds", 7.0)?,
+
+            ng_words: settings.list("ng_words", &["あ", "ん", "ご視聴ありがとうございました"]),
+            ng_rules_path: settings.optional("ng_rules_path")?,
+
+            dictionary_dir: settings.optional("dictionary_dir")?,
+            dictionary: settings.optional("dictionary")?,
+            include_raw: settings.value("dictionary_include_raw", false)?,
+
+            no_speech_threshold: settings.threshold("no_speech_threshold", None)?,
+            logprob_threshold: settings.threshold("logprob_threshold", None)?,
+            compression_ratio_threshold: settings.threshold("compression_ratio_threshold", None)?,
+            repetition_threshold: settings.threshold("repetition_threshold", None)?,
+            filter_action: settings.value("hallucination_action", FilterAction::Drop)?,
+        };
+        config.validate(settings)?;
+        Ok(config)
+    }
+
+    /// Range and consistency checks, with the same limits as session overrides
+    fn validate(&self, settings: &Settings) -> Result<(), String> {
+        let s = settings;
 
-        Self {
-            host,
-            port,
-            whisper_model_path,
-            whisper_language,
-            whisper_threads,
-            whisper_block_seconds,
-            vad_model_path,
-            vad_threshold,
-            vad_min_speech_duration_ms,
-            vad_max_speech_duration_seconds,
-            vad_min_silence_duration_ms,
-            vad_speech_pad_ms,
-            sample_rate,
-            min_speech_samples,
-            max_silence_samples,
-            max_speech_samples,
-            ng_words,
+        s.ensure(
+            is_supported_language(&self.whisper_language),
+            "whisper_language",
+            &self.whisper_language,
+            "unsupported language",
+        )?;
+        if let Some(language) = self
+            .language_candidates
+            .iter()
+            .find(|c| c.as_str() == AUTO_LANGUAGE || !is_supported_language(c))
+        {
+            return Err(s.invalid(
+                "whisper_language_candidates",
+                "",
+                format!("unsupported language {}", language),
+            ));
+        }
+        if let Some(prompt) = &self.whisper_prompt {
+            s.ensure(
+                !prompt.contains('\0'),
+                "whisper_prompt",
+                prompt,
+                "must not contain NUL characters",
+            )?;
+        }
+        s.ensure(
+            self.context_tokens <= MAX_CONTEXT_TOKENS,
+            "context_tokens",
+            self.context_tokens,
+            format!("must be between 0 and {}", MAX_CONTEXT_TOKENS),
+        )?;
+        s.ensure(
+            self.whisper_threads >= 1,
+            "whisper_threads",
+            self.whisper_threads,
+            "must be at least 1",
+        )?;
+        s.ensure(
+            self.whisper_workers >= 1,
+            "whisper_workers",
+            self.whisper_workers,
+            "must be at least 1",
+        )?;
+        s.ensure(
+            (1..=MAX_BLOCK_SECONDS).contains(&self.whisper_block_seconds),
+            "whisper_block_seconds",
+            self.whisper_block_seconds,
+            format!("must be between 1 and {}", MAX_BLOCK_SECONDS),
+        )?;
+
+        s.ensure(
+            self.vad_threshold > 0.0 && self.vad_threshold < 1.0,
+            "vad_threshold",
+            self.vad_threshold,
+            "must be between 0 and 1",
+        )?;
+        for (key, value, max) in [
+            (
+                "vad_min_speech_duration_ms",
+                self.vad_min_speech_duration_ms,
+                MAX_DURATION_MS,
+            ),
+            (
+                "vad_min_silence_duration_ms",
+                self.vad_min_silence_duration_ms,
+                MAX_DURATION_MS,
+            ),
+            (
+                "vad_speech_pad_ms",
+                self.vad_speech_pad_ms,
+                MAX_SPEECH_PAD_MS,
+            ),
+        ] {
+            s.ensure(
+                (0..=max).contains(&value),
+                key,
+                value,
+                format!("must be between 0 and {}", max),
+            )?;
+        }
+        s.ensure(
+            self.vad_max_speech_duration_seconds > 0.0,
+            "vad_max_speech_duration_seconds",
+            self.vad_max_speech_duration_seconds,
+            "must be positive",
+        )?;
+
+        s.ensure(
+            (MIN_INPUT_SAMPLE_RATE..=MAX_INPUT_SAMPLE_RATE).contains(&self.sample_rate),
+            "sample_rate",
+            self.sample_rate,
+            format!(
+                "must be between {} and {}",
+                MIN_INPUT_SAMPLE_RATE, MAX_INPUT_SAMPLE_RATE
+            ),
+        )?;
+        s.ensure(
+            (1..=MAX_CHANNELS).contains(&self.channels),
+            "audio_channels",
+            self.channels,
+            format!("must be between 1 and {}", MAX_CHANNELS),
+        )?;
+
+        let block_size = PIPELINE_SAMPLE_RATE as usize * self.whisper_block_seconds;
+        s.ensure(
+            self.min_speech_samples < block_size,
+            "min_speech_samples",
+            self.min_speech_samples,
+            format!(
+                "must be smaller than the block size ({} samples)",
+                block_size
+            ),
+        )?;
+        s.ensure(
+            self.max_in_flight >= 1,
+            "max_in_flight",
+            self.max_in_flight,
+            "must be at least 1",
+        )?;
+        s.ensure(
+            self.partial_interval_ms == 0
+                || (MIN_PARTIAL_INTERVAL_MS..=MAX_DURATION_MS as u64)
+                    .contains(&self.partial_interval_ms),
+            "partial_interval_ms",
+            self.partial_interval_ms,
+            format!(
+                "must be 0 or between {} and {}",
+                MIN_PARTIAL_INTERVAL_MS, MAX_DURATION_MS
+            ),
+        )?;
+
+        s.ensure(
+            self.subtitle_max_line_width >= 1,
+            "subtitle_max_line_width",
+            self.subtitle_max_line_width,
+            "must be at least 1",
+        )?;
+        s.ensure(
+            (1..=MAX_SUBTITLE_LINES).contains(&self.subtitle_max_lines),
+            "subtitle_max_lines",
+            self.subtitle_max_lines,
+            format!("must be between 1 and {}", MAX_SUBTITLE_LINES),
+        )?;
+        s.ensure(
+            self.subtitle_max_cue_seconds > 0.0 && self.subtitle_max_cue_seconds.is_finite(),
+            "subtitle_max_cue_seconds",
+            self.subtitle_max_cue_seconds,
+            "must be a positive number",
+        )?;
+
+        if let Some(threshold) = self.no_speech_threshold {
+            s.ensure(
+                (0.0..=1.0).contains(&threshold),
+                "no_speech_threshold",
+                threshold,
+                "must be between 0 and 1, or off",
+            )?;
+        }
+        if let Some(threshold) = self.logprob_threshold {
+            s.ensure(
+                threshold <= 0.0,
+                "logprob_threshold",
+                threshold,
+                "must be 0 or below, or off",
+            )?;
+        }
+        if let Some(threshold) = self.compression_ratio_threshold {
+            s.ensure(
+                threshold > 0.0 && threshold.is_finite(),
+                "compression_ratio_threshold",
+                threshold,
+                "must be a positive number, or off",
+            )?;
+        }
+        if let Some(threshold) = self.repetition_threshold {
+            s.ensure(
+                threshold >= 2,
+                "repetition_threshold",
+                threshold,
+                "must be at least 2, or off",
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Checks that the models and the other configured paths exist
+    pub fn check_files(&self) -> Result<(), String> {
+        let vad_model = (self.vad_backend == VadBackend::Silero).then_some(&self.vad_model_path);
+        let files = [
+            ("whisper_model_path", Some(&self.whisper_model_path)),
+            ("vad_model_path", vad_model),
+            ("ng_rules_path", self.ng_rules_path.as_ref()),
+        ];
+        for (key, path) in files {
+            if let Some(path) = path
+                && !Path::new(path).is_file()
+            {
+                return Err(format!("Invalid {} = {:?}: file not found", key, path));
+            }
+        }
+        if let Some(dir) = &self.dictionary_dir
+            && !Path::new(dir).is_dir()
+        {
+            return Err(format!(
+                "Invalid dictionary_dir = {:?}: directory not found",
+                dir
+            ));
+        }
+        Ok(())
+    }
+
+    /// Format of the binary audio frames
+    pub fn audio_format(&self) -> AudioFormat {
+        AudioFormat {
+            encoding: self.encoding,
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+        }
+    }
+
+    /// Line wrapping and timing of exported subtitles
+    pub fn subtitle_options(&self) -> SubtitleOptions {
+        SubtitleOptions {
+            max_line_width: self.subtitle_max_line_width,
+            max_lines: self.subtitle_max_lines,
+            max_cue_seconds: self.subtitle_max_cue_seconds,
         }
     }
 
     pub fn print_config(&self) {
         println!("📋 Configuration:");
         println!("  Server: {}:{}", self.host, self.port);
+        if self.http_port == 0 {
+            println!("  HTTP API: disabled");
+        } else {
+            println!("  HTTP API: {}:{}", self.host, self.http_port);
+        }
         println!("  Whisper Model: {}", self.whisper_model_path);
         println!("  Whisper Language: {}", self.whisper_language);
+        if !self.language_candidates.is_empty() {
+            println!("  Language Candidates: {:?}", self.language_candidates);
+        }
+        println!("  Whisper Task: {}", self.whisper_task);
+        if self.whisper_task == WhisperTask::Translate {
+            println!("  Include Source Transcript: {}", self.include_source);
+        }
+        if let Some(prompt) = &self.whisper_prompt {
+            println!("  Whisper Prompt: {}", prompt);
+        }
+        println!("  Context Tokens: {}", self.context_tokens);
         println!("  Whisper Threads: {}", self.whisper_threads);
+        println!("  Whisper Workers: {}", self.whisper_workers);
         println!("  Whisper Block: {}s", self.whisper_block_seconds);
-        println!("  VAD Model: {}", self.vad_model_path);
+        match self.vad_backend {
+            VadBackend::Silero => println!("  VAD: silero ({})", self.vad_model_path),
+            VadBackend::Energy => println!("  VAD: energy"),
+        }
         println!("  VAD Threshold: {}", self.vad_threshold);
         println!("  VAD Min Speech: {}ms", self.vad_min_speech_duration_ms);
         println!(
@@ -138,7 +474,365 @@ impl Config {
         );
         println!("  VAD Min Silence: {}ms", self.vad_min_silence_duration_ms);
         println!("  VAD Speech Pad: {}ms", self.vad_speech_pad_ms);
-        println!("  Sample Rate: {}Hz", self.sample_rate);
+        println!(
+            "  Audio Input: {} {}Hz {}ch",
+            self.encoding, self.sample_rate, self.channels
+        );
+        println!("  Max In-Flight Utterances: {}", self.max_in_flight);
+        println!(
+            "  Transcribe On Disconnect: {}",
+            self.transcribe_on_disconnect
+        );
+        println!("  Partial Interval: {}ms", self.partial_interval_ms);
+        println!("  Word Timestamps: {}", self.word_timestamps);
+        println!(
+            "  Subtitles: {} columns x {} lines, max {}s per cue",
+            self.subtitle_max_line_width, self.subtitle_max_lines, self.subtitle_max_cue_seconds
+        );
         println!("  NG Words: {:?}", self.ng_words);
+        if let Some(path) = &self.ng_rules_path {
+            println!("  NG Rules: {}", path);
+        }
+        if let Some(dir) = &self.dictionary_dir {
+            println!(
+                "  Dictionaries: {} (default: {}, include raw: {})",
+                dir,
+                self.dictionary.as_deref().unwrap_or("none"),
+                self.include_raw
+            );
+        }
+        println!(
+            "  Hallucination Filters: no_speech > {} / avg_logprob < {} / compression_ratio > {} / repetition >= {} ({})",
+            display_threshold(self.no_speech_threshold),
+            display_threshold(self.logprob_threshold),
+            display_threshold(self.compression_ratio_threshold),
+            display_threshold(self.repetition_threshold),
+            self.filter_action
+        );
+    }
+}
+
+fn display_threshold<T: fmt::Display>(threshold: Option<T>) -> String {
+    threshold.map_or_else(|| "off".to_string(), |t| t.to_string())
+}
+
+/// Where a setting was given
+#[derive(Debug, Clone, PartialEq)]
+enum Source {
+    File(String),
+    Env,
+    Flag(String),
+}
+
+/// Value as written, before it is parsed
+#[derive(Debug, Clone, PartialEq)]
+enum RawValue {
+    Text(String),
+    // TOML array, only for list settings
+    List(Vec<String>),
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    value: RawValue,
+    source: Source,
+}
+
+/// Raw settings from the config file, environment variables and command-line flags.
+/// Later sources replace earlier ones; anything not given keeps its default.
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    entries: HashMap<String, Entry>,
+}
+
+impl Settings {
+    /// Reads a TOML file of `key = value` lines
+    pub fn load_file(&mut self, path: &str) -> Result<(), String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config file {}: {}", path, e))?;
+        self.parse_file(&content, path)
+    }
+
+    fn parse_file(&mut self, content: &str, path: &str) -> Result<(), String> {
+        let table: toml::Table = content
+            .parse()
+            .map_err(|e| format!("Invalid config file {}: {}", path, e))?;
+
+        for (key, value) in table {
+            if !KEYS.contains(&key.as_str()) {
+                return Err(format!("Unknown key {} in config file {}", key, path));
+            }
+            let value = match value {
+                toml::Value::Array(items) => RawValue::List(
+                    items
+                        .into_iter()
+                        .map(toml_text)
+                        .collect::<Option<_>>()
+                        .ok_or_else(|| {
+                            format!(
+                                "Invalid {} in config file {}: expected a list of strings",
+                                key, path
+                            )
+                        })?,
+                ),
+                value => RawValue::Text(toml_text(value).ok_or_else(|| {
+                    format!(
+                        "Invalid {} in config file {}: expected a string, number or boolean",
+                        key, path
+                    )
+                })?),
+            };
+            let source = Source::File(path.to_string());
+            self.entries.insert(key, Entry { value, source });
+        }
+        Ok(())
+    }
+
+    /// Takes every setting that has an environment variable
+    pub fn load_env(&mut self, lookup: impl Fn(&str) -> Option<String>) {
+        for key in KEYS {
+            if let Some(value) = lookup(&key.to_ascii_uppercase()) {
+                let entry = Entry {
+                    value: RawValue::Text(value),
+                    source: Source::Env,
+                };
+                self.entries.insert(key.to_string(), entry);
+            }
+        }
+    }
+
+    /// Sets one value from a command-line flag; the key may be in either case
+    pub fn set(&mut self, key: &str, value: &str, flag: &str) -> Result<(), String> {
+        let key = key.trim().to_ascii_lowercase();
+        if !KEYS.contains(&key.as_str()) {
+            return Err(format!("Unknown key {} in {}", key, flag));
+        }
+        let entry = Entry {
+            value: RawValue::Text(value.to_string()),
+            source: Source::Flag(flag.to_string()),
+        };
+        self.entries.insert(key, entry);
+        Ok(())
+    }
+
+    fn text(&self, key: &str) -> Result<Option<&str>, String> {
+        match self.entries.get(key).map(|entry| &entry.value) {
+            None => Ok(None),
+            Some(RawValue::Text(text)) => Ok(Some(text.trim())),
+            Some(RawValue::List(_)) => Err(self.invalid(key, "", "expected a single value")),
+        }
+    }
+
+    /// Parsed value, or `default` when the setting is not given
+    fn value<T: FromStr>(&self, key: &str, default: T) -> Result<T, String>
+    where
+        T::Err: fmt::Display,
+    {
+        match self.text(key)? {
+            Some(text) => text.parse().map_err(|e| self.invalid(key, "", e)),
+            None => Ok(default),
+        }
+    }
+
+    /// Text setting where an empty value means unset
+    fn optional(&self, key: &str) -> Result<Option<String>, String> {
+        Ok(self
+            .text(key)?
+            .filter(|text| !text.is_empty())
+            .map(str::to_string))
+    }
+
+    /// Threshold where `off` or an empty value disables the check
+    fn threshold<T: FromStr>(&self, key: &str, default: Option<T>) -> Result<Option<T>, String>
+    where
+        T::Err: fmt::Display,
+    {
+        match self.text(key)? {
+            Some(text) if text.is_empty() || text.eq_ignore_ascii_case("off") => Ok(None),
+            Some(text) => text.parse().map(Some).map_err(|e| self.invalid(key, "", e)),
+            None => Ok(default),
+        }
+    }
+
+    /// TOML array or comma-separated text, without empty items
+    fn list(&self, key: &str, default: &[&str]) -> Vec<String> {
+        let items: Vec<String> = match self.entries.get(key).map(|entry| &entry.value) {
+            Some(RawValue::List(items)) => items.clone(),
+            Some(RawValue::Text(text)) => text.split(',').map(str::to_string).collect(),
+            None => default.iter().map(|item| item.to_string()).collect(),
+        };
+        items
+            .into_iter()
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect()
+    }
+
+    /// Error naming the setting, its value and where it came from.
+    /// `default` is shown when the setting was not given.
+    fn invalid(&self, key: &str, default: impl fmt::Display, problem: impl fmt::Display) -> String {
+        match self.entries.get(key) {
+            Some(entry) => {
+                // Environment variables are named in upper case
+                let name = match entry.source {
+                    Source::Env => key.to_ascii_uppercase(),
+                    _ => key.to_string(),
+                };
+                format!(
+                    "Invalid {} = {} ({}): {}",
+                    name, entry.value, entry.source, problem
+                )
+            }
+            None => format!("Invalid {} = {} (default): {}", key, default, problem),
+        }
+    }
+
+    /// Fails with [`Settings::invalid`] unless `ok`
+    fn ensure(
+        &self,
+        ok: bool,
+        key: &str,
+        value: impl fmt::Display,
+        problem: impl fmt::Display,
+    ) -> Result<(), String> {
+        if ok {
+            Ok(())
+        } else {
+            Err(self.invalid(key, value, problem))
+        }
+    }
+}
+
+/// Strings, numbers and booleans as text
+fn toml_text(value: toml::Value) -> Option<String> {
+    match value {
+        toml::Value::String(text) => Some(text),
+        toml::Value::Integer(n) => Some(n.to_string()),
+        toml::Value::Float(n) => Some(n.to_string()),
+        toml::Value::Boolean(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+impl fmt::Display for RawValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RawValue::Text(text) => write!(f, "{:?}", text),
+            RawValue::List(items) => write!(f, "{:?}", items),
+        }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::File(path) => write!(f, "config file {}", path),
+            Source::Env => write!(f, "environment"),
+            Source::Flag(flag) => write!(f, "flag {}", flag),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        move |key| vars.get(key).cloned()
+    }
+
+    #[test]
+    fn later_sources_replace_earlier_ones() {
+        let mut settings = Settings::default();
+        settings
+            .parse_file(
+                "port = 9100\nvad_threshold = 0.6\nng_words = [\"えー\", \"あの\"]\nlogprob_threshold = -1.0",
+                "server.toml",
+            )
+            .unwrap();
+        settings.load_env(env(&[("PORT", "9200"), ("LOGPROB_THRESHOLD", "off")]));
+        settings.set("port", "9300", "--port").unwrap();
+
+        let config = Config::from_settings(&settings).unwrap();
+        assert_eq!(config.port, 9300);
+        assert_eq!(config.vad_threshold, 0.6);
+        assert_eq!(config.ng_words, vec!["えー", "あの"]);
+        assert_eq!(config.logprob_threshold, None);
+        // Not given anywhere
+        assert_eq!(config.http_port, 9001);
+        assert_eq!(config.repetition_threshold, None);
+    }
+
+    #[test]
+    fn unparsable_values_are_errors() {
+        let mut settings = Settings::default();
+        settings.load_env(env(&[("PORT", "90o0")]));
+        assert_eq!(
+            Config::from_settings(&settings).unwrap_err(),
+            "Invalid PORT = \"90o0\" (environment): invalid digit found in string"
+        );
+
+        let mut settings = Settings::default();
+        settings.set("VAD_THRESHOLD", "abc", "--set").unwrap();
+        let error = Config::from_settings(&settings).unwrap_err();
+        assert!(error.starts_with("Invalid vad_threshold = \"abc\" (flag --set)"));
+
+        let mut settings = Settings::default();
+        settings.load_env(env(&[("COMPRESSION_RATIO_THRESHOLD", "disabled")]));
+        assert!(Config::from_settings(&settings).is_err());
+    }
+
+    #[test]
+    fn out_of_range_values_are_errors() {
+        let mut settings = Settings::default();
+        settings
+            .parse_file("vad_threshold = 1.5", "server.toml")
+            .unwrap();
+        assert_eq!(
+            Config::from_settings(&settings).unwrap_err(),
+            "Invalid vad_threshold = \"1.5\" (config file server.toml): must be between 0 and 1"
+        );
+
+        let mut settings = Settings::default();
+        settings.load_env(env(&[("SAMPLE_RATE", "4000")]));
+        assert!(Config::from_settings(&settings).is_err());
+
+        // 30-second blocks hold 480000 samples
+        let mut settings = Settings::default();
+        settings.load_env(env(&[("MIN_SPEECH_SAMPLES", "480000")]));
+        assert!(Config::from_settings(&settings).is_err());
+        settings.load_env(env(&[("WHISPER_BLOCK_SECONDS", "60")]));
+        assert!(Config::from_settings(&settings).is_ok());
+    }
+
+    #[test]
+    fn example_file_holds_the_defaults() {
+        let mut settings = Settings::default();
+        settings
+            .parse_file(
+                include_str!("../config.example.toml"),
+                "config.example.toml",
+            )
+            .unwrap();
+        let config = Config::from_settings(&settings).unwrap();
+        assert_eq!(config.vad_max_speech_duration_seconds, f32::INFINITY);
+        assert_eq!(config.no_speech_threshold, None);
+        assert_eq!(config.whisper_prompt, None);
+        assert!(config.language_candidates.is_empty());
+    }
+
+    #[test]
+    fn unknown_keys_are_errors() {
+        let mut settings = Settings::default();
+        assert!(
+            settings
+                .parse_file("vad_treshold = 0.6", "server.toml")
+                .is_err()
+        );
+        assert!(settings.set("vad_treshold", "0.6", "--set").is_err());
     }
 }
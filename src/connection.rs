@@ -0,0 +1,232 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use futures::stream::{SplitSink, SplitStream};
+use futures::{SinkExt, StreamExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::audio::PIPELINE_SAMPLE_RATE;
+use crate::config::Config;
+use crate::pipeline::TranscriptionPipeline;
+use crate::protocol::{ServerMessage, Status};
+use crate::session::{CloseRequest, ControlMessage, ExportRequest, SessionConfig};
+use crate::vad::VadFactory;
+use crate::worker::WorkerPool;
+
+type WsStream = WebSocketStream<TcpStream>;
+
+// Frames buffered between the reader and the VAD stage
+const INBOUND_BUFFER: usize = 256;
+
+/// Runs one WebSocket session.
+///
+/// The connection is split into three stages so that reading never waits on Whisper:
+/// a reader forwarding frames, the pipeline cutting utterances and submitting them
+/// to the worker pool, and a sender writing its results back in utterance order.
+pub async fn handle_connection(
+    ws: WsStream,
+    addr: SocketAddr,
+    config: Config,
+    pool: Arc<WorkerPool>,
+    vad: VadFactory,
+) {
+    let (mut write, read) = ws.split();
+    let (frame_tx, mut frame_rx) = mpsc::channel::<Message>(INBOUND_BUFFER);
+    let reader = tokio::spawn(read_frames(read, frame_tx));
+
+    match TranscriptionPipeline::new(config, pool, vad) {
+        Ok((pipeline, events)) => {
+            println!("✅ VAD initialized");
+            let sender = tokio::spawn(send_responses(write, events));
+            pipeline.send(ServerMessage::status(Status::Ready)).await;
+
+            let mut connection = Connection {
+                pipeline,
+                close_requested: false,
+            };
+            while let Some(msg) = frame_rx.recv().await {
+                if !connection.handle_frame(msg).await {
+                    break;
+                }
+            }
+            connection.close().await;
+
+            // The sender drains the remaining results once the pipeline is gone
+            reader.abort();
+            let _ = sender.await;
+        }
+        Err(e) => {
+            eprintln!("❌ Failed to initialize VAD: {}", e);
+            let _ = write
+                .send(Message::Text(ServerMessage::error(e).to_json().into()))
+                .await;
+            reader.abort();
+        }
+    }
+
+    println!("🔌 Client disconnected: {}", addr);
+}
+
+/// Forwards audio and text frames to the VAD stage until the client goes away.
+/// Ping frames are answered by tungstenite while reading.
+async fn read_frames(mut read: SplitStream<WsStream>, frame_tx: mpsc::Sender<Message>) {
+    while let Some(result) = read.next().await {
+        match result {
+            Ok(Message::Close(_)) => {
+                println!("👋 Client disconnecting");
+                break;
+            }
+            Ok(msg @ (Message::Binary(_) | Message::Text(_))) => {
+                if frame_tx.send(msg).await.is_err() {
+                    break;
+                }
+            }
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("❌ WebSocket read error: {}", e);
+                break;
+            }
+        }
+    }
+}
+
+/// Writes the pipeline's results in order, then a close frame once they are all out.
+/// Once the socket fails or is closed, the remaining results are only logged.
+async fn send_responses(
+    mut write: SplitSink<WsStream, Message>,
+    mut events: mpsc::Receiver<ServerMessage>,
+) {
+    let mut open = true;
+    while let Some(message) = events.recv().await {
+        let res = message.to_json();
+        let preview: String = res.chars().take(100).collect();
+        if !open {
+            println!("📝 Undelivered response: {}", preview);
+            continue;
+        }
+
+        println!("📤 Sending response: {}", preview);
+        if let Err(e) = write.send(Message::Text(res.into())).await {
+            eprintln!("❌ Failed to send response: {}", e);
+            open = false;
+        }
+    }
+
+    if open {
+        let _ = write.send(Message::Close(None)).await;
+    }
+}
+
+/// State of the VAD stage of one connection
+struct Connection {
+    pipeline: TranscriptionPipeline,
+    // Set by a close command; the queued results are still delivered
+    close_requested: bool,
+}
+
+impl Connection {
+    /// Handles one frame; returns false once the client asked to close
+    async fn handle_frame(&mut self, msg: Message) -> bool {
+        match msg {
+            Message::Binary(data) => {
+                // Convert the frame to 16 kHz mono f32 and run it through the VAD
+                let before = self.pipeline.received_samples();
+                if let Err(e) = self.pipeline.push_audio(&data).await {
+                    eprintln!("❌ Invalid audio frame: {}", e);
+                    self.pipeline.send(ServerMessage::error(e)).await;
+                    return true;
+                }
+
+                let received = self.pipeline.received_samples();
+                println!(
+                    "📨 Received {} audio samples (session total {:.2}s)",
+                    received - before,
+                    received as f64 / PIPELINE_SAMPLE_RATE as f64
+                );
+            }
+            Message::Text(text) => {
+                if text == "flush" {
+                    self.pipeline.flush().await;
+                    return true;
+                }
+
+                match serde_json::from_str::<ControlMessage>(&text) {
+                    Ok(ControlMessage::Config(session_config)) => {
+                        self.apply_config(&session_config).await
+                    }
+                    Ok(ControlMessage::Export(request)) => self.export(&request).await,
+                    Ok(ControlMessage::Close(request)) => {
+                        self.request_close(&request).await;
+                        return false;
+                    }
+                    Err(e) => {
+                        // JSON that is not a valid control message gets told what was rejected
+                        let message = if serde_json::from_str::<serde_json::Value>(&text).is_ok() {
+                            format!("Invalid control message: {}", e)
+                        } else {
+                            "Send binary audio data, 'flush' command or a JSON config, export or close message".to_string()
+                        };
+                        self.pipeline.send(ServerMessage::error(message)).await;
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        true
+    }
+
+    async fn apply_config(&mut self, session_config: &SessionConfig) {
+        let response = match self.pipeline.apply_config(session_config) {
+            Ok(settings) => ServerMessage::Status {
+                status: Status::ConfigApplied,
+                config: Some(settings),
+            },
+            Err(e) => {
+                eprintln!("❌ Rejected session config: {}", e);
+                ServerMessage::error(e)
+            }
+        };
+        self.pipeline.send(response).await;
+    }
+
+    /// Queues a subtitle export behind the utterances submitted so far
+    async fn export(&self, request: &ExportRequest) {
+        match request.options(&self.pipeline.config().subtitle_options()) {
+            Ok(options) => self.pipeline.export(request.format, options).await,
+            Err(e) => self.pipeline.send(ServerMessage::error(e)).await,
+        }
+    }
+
+    /// Processes the remaining audio and optionally exports the transcript.
+    /// The socket is closed once the results are out.
+    async fn request_close(&mut self, request: &CloseRequest) {
+        println!("👋 Client requested close");
+        self.close_requested = true;
+        self.pipeline.flush().await;
+
+        if let Some(format) = request.format {
+            let options = self.pipeline.config().subtitle_options();
+            self.pipeline.export(format, options).await;
+        }
+    }
+
+    /// Called once the client is gone or asked to close.
+    /// Either transcribes the trailing audio or cancels all outstanding work.
+    async fn close(self) {
+        // Everything was flushed, and the results are still wanted
+        if self.close_requested {
+            return;
+        }
+
+        if self.pipeline.config().transcribe_on_disconnect {
+            println!("🔄 Processing remaining audio on disconnect");
+            self.pipeline.finish().await;
+        } else {
+            self.pipeline.cancel();
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+// Utterances kept per session; far more text than a prompt can hold
+const MAX_UTTERANCES: usize = 32;
+
+/// Final text of a session's recent utterances, used as the prompt of later ones.
+/// Texts are kept by sequence number, as workers may finish out of order.
+#[derive(Debug, Default)]
+pub struct SessionContext {
+    texts: Mutex<BTreeMap<u64, String>>,
+}
+
+impl SessionContext {
+    /// Records the text of a finished utterance
+    pub fn record(&self, seq: u64, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+
+        let mut texts = self.texts.lock().unwrap();
+        texts.insert(seq, text.to_string());
+        while texts.len() > MAX_UTTERANCES {
+            texts.pop_first();
+        }
+    }
+
+    /// Text of the finished utterances before `seq`, oldest first
+    pub fn before(&self, seq: u64) -> String {
+        let texts = self.texts.lock().unwrap();
+        texts
+            .range(..seq)
+            .map(|(_, text)| text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_before_an_utterance_is_in_order() {
+        let context = SessionContext::default();
+        context.record(2, "three");
+        context.record(0, "one");
+        context.record(1, "");
+        context.record(3, "four");
+
+        assert_eq!(context.before(3), "one three");
+        assert_eq!(context.before(0), "");
+    }
+
+    #[test]
+    fn old_utterances_are_dropped() {
+        let context = SessionContext::default();
+        for seq in 0..MAX_UTTERANCES as u64 + 2 {
+            context.record(seq, &seq.to_string());
+        }
+
+        assert!(context.before(u64::MAX).starts_with("2 3 "));
+    }
+}
@@ -0,0 +1,453 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use regex::Regex;
+use serde::Deserialize;
+
+// How often the dictionary directory is checked for changes
+const RELOAD_INTERVAL: Duration = Duration::from_secs(2);
+
+/// One `pattern -> replacement` pair
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Entry {
+    pattern: String,
+    replacement: String,
+    // Only applies to text in this language; all languages when unset
+    #[serde(default)]
+    language: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DictionaryFile {
+    #[serde(default)]
+    entries: Vec<Entry>,
+}
+
+/// Replaces every pattern in one pass, preferring the longest pattern at each position
+#[derive(Debug)]
+struct Replacer {
+    regex: Regex,
+    replacements: HashMap<String, String>,
+}
+
+impl Replacer {
+    fn new(replacements: HashMap<String, String>) -> Result<Self, String> {
+        let mut patterns: Vec<&String> = replacements.keys().collect();
+        // The regex takes the first alternative that matches
+        patterns.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+        let alternation = patterns
+            .iter()
+            .map(|pattern| regex::escape(pattern))
+            .collect::<Vec<_>>()
+            .join("|");
+        let regex = Regex::new(&alternation).map_err(|e| e.to_string())?;
+        Ok(Self {
+            regex,
+            replacements,
+        })
+    }
+
+    fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        self.regex.replace_all(text, |captures: &regex::Captures| {
+            self.replacements[&captures[0]].clone()
+        })
+    }
+}
+
+/// Corrections for recurring transcription errors, such as names and product codes
+#[derive(Debug, Default)]
+pub struct Dictionary {
+    // Entries for all languages
+    common: Option<Replacer>,
+    // Common entries plus those of one language, which win on the same pattern
+    by_language: HashMap<String, Replacer>,
+}
+
+impl Dictionary {
+    /// Loads a `.csv` file of `pattern,replacement[,language]` rows,
+    /// or a TOML file with `[[entries]]` tables
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let entries = if path.extension().is_some_and(|ext| ext == "csv") {
+            parse_csv(&content)?
+        } else {
+            let file: DictionaryFile = toml::from_str(&content).map_err(|e| e.to_string())?;
+            file.entries
+        };
+        Self::new(entries)
+    }
+
+    fn new(entries: Vec<Entry>) -> Result<Self, String> {
+        let mut common = HashMap::new();
+        let mut languages: HashMap<String, HashMap<String, String>> = HashMap::new();
+        for entry in entries {
+            if entry.pattern.is_empty() {
+                return Err(format!("empty pattern for {:?}", entry.replacement));
+            }
+            match entry.language.filter(|language| !language.is_empty()) {
+                Some(language) => languages
+                    .entry(language)
+                    .or_default()
+                    .insert(entry.pattern, entry.replacement),
+                None => common.insert(entry.pattern, entry.replacement),
+            };
+        }
+
+        let mut by_language = HashMap::new();
+        for (language, entries) in languages {
+            let mut replacements = common.clone();
+            replacements.extend(entries);
+            by_language.insert(language, Replacer::new(replacements)?);
+        }
+        let common = if common.is_empty() {
+            None
+        } else {
+            Some(Replacer::new(common)?)
+        };
+        Ok(Self {
+            common,
+            by_language,
+        })
+    }
+
+    /// Text with the entries for `language` applied
+    pub fn apply<'a>(&self, text: &'a str, language: &str) -> Cow<'a, str> {
+        match self.by_language.get(language).or(self.common.as_ref()) {
+            Some(replacer) => replacer.apply(text),
+            None => Cow::Borrowed(text),
+        }
+    }
+}
+
+/// Parses `pattern,replacement[,language]` rows. Fields may be double-quoted to
+/// contain commas; empty lines and lines starting with `#` are skipped.
+fn parse_csv(content: &str) -> Result<Vec<Entry>, String> {
+    let mut entries = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields = split_csv_line(line).map_err(|e| format!("line {}: {}", index + 1, e))?;
+        match fields.as_slice() {
+            [pattern, replacement] => entries.push(Entry {
+                pattern: pattern.clone(),
+                replacement: replacement.clone(),
+                language: None,
+            }),
+            [pattern, replacement, language] => entries.push(Entry {
+                pattern: pattern.clone(),
+                replacement: replacement.clone(),
+                language: Some(language.trim().to_string()),
+            }),
+            _ => {
+                return Err(format!(
+                    "line {}: expected pattern,replacement[,language]",
+                    index + 1
+                ));
+            }
+        }
+    }
+    Ok(entries)
+}
+
+fn split_csv_line(line: &str) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("unterminated quote".to_string());
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+/// A loaded dictionary file
+struct Loaded {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    dictionary: Arc<Dictionary>,
+}
+
+/// Dictionaries of a directory, named after their files (`names.toml` is `names`).
+/// Files are reloaded when they change.
+#[derive(Default)]
+pub struct Dictionaries {
+    dir: Option<PathBuf>,
+    loaded: RwLock<HashMap<String, Loaded>>,
+}
+
+impl Dictionaries {
+    /// Loads every `.toml` and `.csv` file of `dir`; any invalid file is an error
+    pub fn load(dir: Option<&str>) -> Result<Self, String> {
+        let dictionaries = Self {
+            dir: dir.map(PathBuf::from),
+            loaded: RwLock::default(),
+        };
+        let errors = dictionaries.reload()?;
+        if let Some(error) = errors.into_iter().next() {
+            return Err(error);
+        }
+        Ok(dictionaries)
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<Dictionary>> {
+        let loaded = self.loaded.read().unwrap();
+        loaded.get(name).map(|loaded| loaded.dictionary.clone())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.loaded.read().unwrap().contains_key(name)
+    }
+
+    /// Names of the loaded dictionaries, sorted
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.loaded.read().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Loads new and changed files and forgets removed ones.
+    /// A file that fails to load keeps its previous version; its error is returned.
+    /// So does a name shared by a `.toml` and a `.csv` file, as neither is picked.
+    fn reload(&self) -> Result<Vec<String>, String> {
+        let Some(dir) = &self.dir else {
+            return Ok(Vec::new());
+        };
+        let files = fs::read_dir(dir).map_err(|e| {
+            format!(
+                "Failed to read dictionary directory {}: {}",
+                dir.display(),
+                e
+            )
+        })?;
+
+        let mut errors = Vec::new();
+        let mut found: HashMap<String, (PathBuf, Option<SystemTime>)> = HashMap::new();
+        let mut duplicates = HashSet::new();
+        for file in files.flatten() {
+            let path = file.path();
+            let supported = path
+                .extension()
+                .is_some_and(|ext| ext == "toml" || ext == "csv");
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            if supported && path.is_file() {
+                let modified = file.metadata().and_then(|m| m.modified()).ok();
+                if let Some((other, _)) = found.get(name) {
+                    errors.push(format!(
+                        "Dictionary {} is defined twice: {} and {}",
+                        name,
+                        other.display(),
+                        path.display()
+                    ));
+                    duplicates.insert(name.to_string());
+                } else {
+                    found.insert(name.to_string(), (path, modified));
+                }
+            }
+        }
+
+        let mut changes = Vec::new();
+        {
+            let loaded = self.loaded.read().unwrap();
+            for (name, (path, modified)) in &found {
+                if duplicates.contains(name) {
+                    continue;
+                }
+                let unchanged = loaded
+                    .get(name)
+                    .is_some_and(|old| old.path == *path && old.modified == *modified);
+                if !unchanged {
+                    match Dictionary::load(path) {
+                        Ok(dictionary) => {
+                            changes.push((name.clone(), path.clone(), *modified, dictionary))
+                        }
+                        Err(e) => {
+                            errors.push(format!("Invalid dictionary {}: {}", path.display(), e))
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut loaded = self.loaded.write().unwrap();
+        loaded.retain(|name, _| found.contains_key(name));
+        for (name, path, modified, dictionary) in changes {
+            println!("📖 Loaded dictionary {} from {}", name, path.display());
+            loaded.insert(
+                name,
+                Loaded {
+                    path,
+                    modified,
+                    dictionary: Arc::new(dictionary),
+                },
+            );
+        }
+        Ok(errors)
+    }
+
+    /// Checks the directory for changes until shutdown
+    pub fn watch(self: Arc<Self>, shutdown: Arc<AtomicBool>) {
+        if self.dir.is_none() {
+            return;
+        }
+
+        let spawned = thread::Builder::new()
+            .name("dictionary-watcher".to_string())
+            .spawn(move || {
+                while !shutdown.load(Ordering::SeqCst) {
+                    thread::sleep(RELOAD_INTERVAL);
+                    match self.reload() {
+                        Ok(errors) => {
+                            for error in errors {
+                                eprintln!("⚠️  {}", error);
+                            }
+                        }
+                        Err(e) => eprintln!("⚠️  {}", e),
+                    }
+                }
+            });
+        if let Err(e) = spawned {
+            eprintln!("⚠️  Failed to start dictionary watcher: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(pattern: &str, replacement: &str, language: Option<&str>) -> Entry {
+        Entry {
+            pattern: pattern.to_string(),
+            replacement: replacement.to_string(),
+            language: language.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn longest_pattern_wins_in_a_single_pass() {
+        let dictionary = Dictionary::new(vec![
+            entry("ウィスパー", "Whisper", None),
+            entry("ウィスパーサーバー", "whisper-server-ws", None),
+            // Replacements are not matched again
+            entry("Whisper", "WHISPER", None),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            dictionary.apply("ウィスパーサーバーとウィスパー", "ja"),
+            "whisper-server-wsとWhisper"
+        );
+        assert!(matches!(
+            dictionary.apply("変更なし", "ja"),
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn language_entries_override_common_ones() {
+        let dictionary = Dictionary::new(vec![
+            entry("sku 12", "SKU-12", None),
+            entry("渡辺", "渡邊", Some("ja")),
+            entry("sku 12", "SKU-0012", Some("en")),
+        ])
+        .unwrap();
+
+        assert_eq!(dictionary.apply("渡辺さん sku 12", "ja"), "渡邊さん SKU-12");
+        assert_eq!(dictionary.apply("渡辺 sku 12", "en"), "渡辺 SKU-0012");
+        assert_eq!(dictionary.apply("渡辺 sku 12", "zh"), "渡辺 SKU-12");
+    }
+
+    #[test]
+    fn csv_rows_allow_quotes_and_languages() {
+        let entries =
+            parse_csv("# comment\n\nアクメ,ACME\n\"a, b\",\"say \"\"hi\"\"\",en\n").unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].pattern, "a, b");
+        assert_eq!(entries[1].replacement, "say \"hi\"");
+        assert_eq!(entries[1].language.as_deref(), Some("en"));
+
+        assert!(parse_csv("only-one-field").is_err());
+        assert!(parse_csv("\"open,quote").is_err());
+    }
+
+    #[test]
+    fn directory_is_reloaded_when_files_change() {
+        let dir = std::env::temp_dir().join(format!("dictionaries-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("names.toml");
+        fs::write(
+            &path,
+            "[[entries]]\npattern = \"アクメ\"\nreplacement = \"ACME\"\n",
+        )
+        .unwrap();
+
+        let dictionaries = Dictionaries::load(dir.to_str()).unwrap();
+        assert_eq!(dictionaries.names(), vec!["names"]);
+        let names = dictionaries.get("names").unwrap();
+        assert_eq!(names.apply("アクメ社", "ja"), "ACME社");
+
+        fs::write(&path, "アクメ,Acme Corp.\n").unwrap();
+        fs::rename(&path, dir.join("names.csv")).unwrap();
+        assert!(dictionaries.reload().unwrap().is_empty());
+        let names = dictionaries.get("names").unwrap();
+        assert_eq!(names.apply("アクメ社", "ja"), "Acme Corp.社");
+
+        fs::remove_file(dir.join("names.csv")).unwrap();
+        dictionaries.reload().unwrap();
+        assert!(!dictionaries.contains("names"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn one_name_in_two_files_is_an_error() {
+        let dir = std::env::temp_dir().join(format!("dictionaries-twice-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("names.csv"), "アクメ,ACME\n").unwrap();
+
+        let dictionaries = Dictionaries::load(dir.to_str()).unwrap();
+        fs::write(
+            dir.join("names.toml"),
+            "[[entries]]\npattern = \"アクメ\"\nreplacement = \"Acme Corp.\"\n",
+        )
+        .unwrap();
+        let errors = dictionaries.reload().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("Dictionary names is defined twice"));
+        // The loaded version stays until the clash is resolved
+        let names = dictionaries.get("names").unwrap();
+        assert_eq!(names.apply("アクメ社", "ja"), "ACME社");
+
+        assert!(Dictionaries::load(dir.to_str()).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,321 @@
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::ng::NgRules;
+
+/// Why a segment was filtered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterReason {
+    /// Matches an NG word
+    NgWord,
+    /// A unit of text repeats too many times in a row
+    Repetition,
+    /// The text compresses too well, as looping output does
+    CompressionRatio,
+    /// Whisper thinks there is no speech
+    NoSpeech,
+    /// Whisper is unsure of the tokens
+    AvgLogprob,
+}
+
+/// What happens to a segment caught by a hallucination filter
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FilterAction {
+    /// Removed from the text, and listed under `filtered`
+    #[default]
+    Drop,
+    /// Kept, with the reason in the segment's `filter` field
+    Flag,
+}
+
+impl FromStr for FilterAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "drop" => Ok(FilterAction::Drop),
+            "flag" => Ok(FilterAction::Flag),
+            _ => Err(format!(
+                "Unsupported filter action: {} (expected drop or flag)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for FilterAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterAction::Drop => write!(f, "drop"),
+            FilterAction::Flag => write!(f, "flag"),
+        }
+    }
+}
+
+/// Decoder statistics of one segment
+#[derive(Debug, Clone, Copy)]
+pub struct SegmentStats {
+    pub no_speech_probability: f32,
+    // Mean log probability of the segment's text tokens
+    pub avg_logprob: f32,
+}
+
+/// Outcome of filtering one segment
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    /// Text after the NG rules, and the failed check when filters only flag
+    Keep {
+        text: String,
+        flag: Option<FilterReason>,
+    },
+    Drop(FilterReason),
+}
+
+/// NG rules and hallucination checks applied to every segment.
+/// A threshold of `None` disables its check.
+#[derive(Debug, Clone)]
+pub struct SegmentFilter {
+    ng_rules: NgRules,
+    no_speech_threshold: Option<f32>,
+    logprob_threshold: Option<f32>,
+    compression_ratio_threshold: Option<f32>,
+    repetition_threshold: Option<usize>,
+    action: FilterAction,
+}
+
+impl SegmentFilter {
+    /// Builds the filter, loading `NG_RULES_PATH` after the `NG_WORDS` rules
+    pub fn from_config(config: &Config) -> Result<Self, String> {
+        let mut ng_rules = NgRules::from_words(&config.ng_words);
+        if let Some(path) = &config.ng_rules_path {
+            let rules = NgRules::load(path)?;
+            println!("🚫 Loaded {} NG rule(s) from {}", rules.len(), path);
+            ng_rules.extend(rules);
+        }
+
+        Ok(Self {
+            ng_rules,
+            no_speech_threshold: config.no_speech_threshold,
+            logprob_threshold: config.logprob_threshold,
+            compression_ratio_threshold: config.compression_ratio_threshold,
+            repetition_threshold: config.repetition_threshold,
+            action: config.filter_action,
+        })
+    }
+
+    /// Applies the NG rules, then the hallucination checks to the remaining text.
+    /// NG drops always drop; other checks drop or flag depending on the action.
+    pub fn apply(&self, text: &str, stats: &SegmentStats) -> Verdict {
+        let Some(text) = self.ng_rules.apply(text) else {
+            return Verdict::Drop(FilterReason::NgWord);
+        };
+        match (self.check(&text, stats), self.action) {
+            (Some(reason), FilterAction::Drop) => Verdict::Drop(reason),
+            (flag, _) => Verdict::Keep { text, flag },
+        }
+    }
+
+    /// Returns the first hallucination check the segment fails
+    fn check(&self, text: &str, stats: &SegmentStats) -> Option<FilterReason> {
+        if let Some(threshold) = self.repetition_threshold
+            && longest_repeat(&letters(text)) >= threshold
+        {
+            return Some(FilterReason::Repetition);
+        }
+        if let Some(threshold) = self.compression_ratio_threshold
+            && compression_ratio(text) > threshold
+        {
+            return Some(FilterReason::CompressionRatio);
+        }
+        if let Some(threshold) = self.no_speech_threshold
+            && stats.no_speech_probability > threshold
+        {
+            return Some(FilterReason::NoSpeech);
+        }
+        if let Some(threshold) = self.logprob_threshold
+            && stats.avg_logprob < threshold
+        {
+            return Some(FilterReason::AvgLogprob);
+        }
+        None
+    }
+}
+
+/// Letters only; digits are left out so numbers like "10000" are not repeats
+fn letters(text: &str) -> String {
+    text.chars().filter(|c| c.is_alphabetic()).collect()
+}
+
+/// Size of the UTF-8 text over its zlib-compressed size, as in OpenAI's Whisper
+pub fn compression_ratio(text: &str) -> f32 {
+    if text.is_empty() {
+        return 0.0;
+    }
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    let compressed = encoder
+        .write_all(text.as_bytes())
+        .and_then(|_| encoder.finish());
+    match compressed {
+        Ok(compressed) => text.len() as f32 / compressed.len() as f32,
+        Err(_) => 0.0,
+    }
+}
+
+/// Highest number of back-to-back copies of any piece of two or more characters,
+/// e.g. 3 for "はいはいはい" or "abcabcabcx". Pieces that are themselves a repeat of
+/// a shorter one are skipped, so runs of one character like "すごーーーーーーい" or
+/// "ははははははは" are not counted.
+fn longest_repeat(text: &str) -> usize {
+    let chars: Vec<char> = text.chars().collect();
+    let mut longest = usize::from(!chars.is_empty());
+
+    for unit in 2..=chars.len() / 2 {
+        // Length of the current run of characters equal to the one `unit` before,
+        // and whether the piece it repeats is primitive
+        let mut run = 0;
+        let mut primitive = false;
+        for i in unit..chars.len() {
+            if chars[i] == chars[i - unit] {
+                if run == 0 {
+                    primitive = is_primitive(&chars[i - unit..i]);
+                }
+                run += 1;
+                if primitive {
+                    longest = longest.max(run / unit + 1);
+                }
+            } else {
+                run = 0;
+            }
+        }
+    }
+    longest
+}
+
+/// Whether `piece` is not a whole number of copies of a shorter piece
+fn is_primitive(piece: &[char]) -> bool {
+    (1..piece.len())
+        .filter(|&period| piece.len().is_multiple_of(period))
+        .all(|period| piece[period..].iter().zip(piece).any(|(a, b)| a != b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATS: SegmentStats = SegmentStats {
+        no_speech_probability: 0.1,
+        avg_logprob: -0.3,
+    };
+
+    fn filter() -> SegmentFilter {
+        SegmentFilter {
+            ng_rules: NgRules::from_words(&["ご視聴ありがとうございました".to_string()]),
+            no_speech_threshold: Some(0.6),
+            logprob_threshold: Some(-1.0),
+            compression_ratio_threshold: Some(2.4),
+            repetition_threshold: Some(3),
+            action: FilterAction::Drop,
+        }
+    }
+
+    fn kept(text: &str, flag: Option<FilterReason>) -> Verdict {
+        Verdict::Keep {
+            text: text.to_string(),
+            flag,
+        }
+    }
+
+    #[test]
+    fn ng_words_ignore_punctuation() {
+        let filter = filter();
+        for text in [
+            "ご視聴ありがとうございました",
+            "ご視聴ありがとうございました。",
+            "ご視聴、ありがとうございました！",
+        ] {
+            assert_eq!(
+                filter.apply(text, &STATS),
+                Verdict::Drop(FilterReason::NgWord)
+            );
+        }
+        assert_eq!(
+            filter.apply("ご視聴ありがとう", &STATS),
+            kept("ご視聴ありがとう", None)
+        );
+    }
+
+    #[test]
+    fn flag_action_keeps_the_segment() {
+        let filter = SegmentFilter {
+            action: FilterAction::Flag,
+            ..filter()
+        };
+        assert_eq!(
+            filter.apply("はい。はい。はい。", &STATS),
+            kept("はい。はい。はい。", Some(FilterReason::Repetition))
+        );
+        assert_eq!(
+            filter.apply("ご視聴ありがとうございました。", &STATS),
+            Verdict::Drop(FilterReason::NgWord)
+        );
+    }
+
+    #[test]
+    fn repeated_units_are_caught() {
+        assert_eq!(longest_repeat(&letters("はい。はい。はい。")), 3);
+        assert_eq!(longest_repeat("今日はabcabcabcabcです"), 4);
+        assert_eq!(longest_repeat("こんにちは"), 1);
+        assert_eq!(longest_repeat("すごーーーい"), 1);
+        assert_eq!(longest_repeat("あはははははははは"), 1);
+        assert_eq!(longest_repeat("ははははははははははは"), 1);
+        assert_eq!(longest_repeat("すごーーーーーーーーい"), 1);
+        assert_eq!(longest_repeat("ははっははっははっ"), 3);
+        assert_eq!(longest_repeat(&letters("100000000円")), 1);
+        assert_eq!(longest_repeat(""), 0);
+
+        let filter = filter();
+        assert_eq!(
+            filter.check("はい。はい。はい。", &STATS),
+            Some(FilterReason::Repetition)
+        );
+        assert_eq!(filter.check("はい、そうです。", &STATS), None);
+        assert_eq!(filter.check("あははははははははは！", &STATS), None);
+        assert_eq!(filter.check("すごーーーーーーーーい", &STATS), None);
+    }
+
+    #[test]
+    fn looping_text_compresses_well() {
+        let looping = "I don't know what to say. ".repeat(6);
+        assert!(compression_ratio(&looping) > 2.4);
+        assert!(compression_ratio("The quick brown fox jumps over the lazy dog.") < 2.4);
+    }
+
+    #[test]
+    fn decoder_statistics_are_checked() {
+        let filter = filter();
+        let silent = SegmentStats {
+            no_speech_probability: 0.9,
+            ..STATS
+        };
+        let unsure = SegmentStats {
+            avg_logprob: -1.5,
+            ..STATS
+        };
+        assert_eq!(
+            filter.check("こんにちは", &silent),
+            Some(FilterReason::NoSpeech)
+        );
+        assert_eq!(
+            filter.check("こんにちは", &unsure),
+            Some(FilterReason::AvgLogprob)
+        );
+    }
+}
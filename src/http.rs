@@ -0,0 +1,520 @@
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use axum::extract::multipart::MultipartError;
+use axum::extract::{DefaultBodyLimit, Multipart, State};
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use axum::{Json, Router};
+use serde::Serialize;
+use serde_json::json;
+use tokio::net::TcpListener;
+
+use crate::audio::decode_wav;
+use crate::batch::{BatchTranscription, transcribe_recording};
+use crate::config::Config;
+use crate::context::SessionContext;
+use crate::subtitle::{SubtitleOptions, to_srt, to_vtt};
+use crate::vad::VadFactory;
+use crate::worker::{DecodeOptions, WhisperTask, WorkerPool, is_supported_language};
+
+// Same upload limit as the OpenAI API
+const MAX_UPLOAD_BYTES: usize = 25 * 1024 * 1024;
+// OpenAI's model name, accepted for the loaded model so OpenAI clients work unchanged
+const OPENAI_MODEL: &str = "whisper-1";
+
+#[derive(Clone)]
+struct HttpState {
+    config: Arc<Config>,
+    pool: Arc<WorkerPool>,
+    vad: VadFactory,
+}
+
+/// Serves the OpenAI-compatible HTTP API until the listener fails
+pub async fn serve(listener: TcpListener, config: Config, pool: Arc<WorkerPool>, vad: VadFactory) {
+    let state = HttpState {
+        config: Arc::new(config),
+        pool,
+        vad,
+    };
+
+    let app = Router::new()
+        .route("/v1/audio/transcriptions", post(transcriptions))
+        .route("/v1/audio/translations", post(translations))
+        .layer(DefaultBodyLimit::max(MAX_UPLOAD_BYTES))
+        .with_state(state);
+
+    if let Err(e) = axum::serve(listener, app).await {
+        eprintln!("❌ HTTP server error: {}", e);
+    }
+}
+
+/// Error in the OpenAI error format
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<MultipartError> for ApiError {
+    fn from(e: MultipartError) -> Self {
+        Self {
+            status: e.status(),
+            message: e.body_text(),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let kind = if self.status.is_server_error() {
+            "server_error"
+        } else {
+            "invalid_request_error"
+        };
+        let body = json!({
+            "error": {
+                "message": self.message,
+                "type": kind,
+                "param": null,
+                "code": null,
+            }
+        });
+        (self.status, Json(body)).into_response()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ResponseFormat {
+    Json,
+    VerboseJson,
+    Text,
+    Srt,
+    Vtt,
+}
+
+impl FromStr for ResponseFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(ResponseFormat::Json),
+            "verbose_json" => Ok(ResponseFormat::VerboseJson),
+            "text" => Ok(ResponseFormat::Text),
+            "srt" => Ok(ResponseFormat::Srt),
+            "vtt" => Ok(ResponseFormat::Vtt),
+            _ => Err(format!(
+                "Unsupported response_format: {} (expected json, verbose_json, text, srt or vtt)",
+                s
+            )),
+        }
+    }
+}
+
+/// Form fields of a transcription request
+struct TranscriptionRequest {
+    file: Vec<u8>,
+    model: Option<String>,
+    language: Option<String>,
+    prompt: Option<String>,
+    temperature: Option<f32>,
+    response_format: ResponseFormat,
+    // Set by timestamp_granularities[]=word
+    word_timestamps: bool,
+}
+
+impl TranscriptionRequest {
+    async fn from_multipart(mut multipart: Multipart) -> Result<Self, ApiError> {
+        let mut file = None;
+        let mut request = Self {
+            file: Vec::new(),
+            model: None,
+            language: None,
+            prompt: None,
+            temperature: None,
+            response_format: ResponseFormat::Json,
+            word_timestamps: false,
+        };
+
+        while let Some(field) = multipart.next_field().await? {
+            let name = field.name().unwrap_or_default().to_string();
+            match name.as_str() {
+                "file" => file = Some(field.bytes().await?.to_vec()),
+                "model" => request.model = Some(field.text().await?),
+                "language" => request.language = Some(field.text().await?),
+                "prompt" => request.prompt = Some(field.text().await?),
+                "temperature" => {
+                    let value = field.text().await?;
+                    let temperature = value
+                        .trim()
+                        .parse::<f32>()
+                        .ok()
+                        .filter(|t| (0.0..=1.0).contains(t))
+                        .ok_or_else(|| {
+                            ApiError::bad_request(format!(
+                                "temperature must be between 0 and 1 (got {})",
+                                value
+                            ))
+                        })?;
+                    request.temperature = Some(temperature);
+                }
+                "response_format" => {
+                    request.response_format = field
+                        .text()
+                        .await?
+                        .trim()
+                        .parse()
+                        .map_err(ApiError::bad_request)?;
+                }
+                "timestamp_granularities[]" | "timestamp_granularities" => {
+                    match field.text().await?.trim() {
+                        "word" => request.word_timestamps = true,
+                        "segment" => {}
+                        other => {
+                            return Err(ApiError::bad_request(format!(
+                                "Unsupported timestamp granularity: {} (expected word or segment)",
+                                other
+                            )));
+                        }
+                    }
+                }
+                // Other OpenAI fields are accepted and ignored
+                _ => {}
+            }
+        }
+
+        request.file = file.ok_or_else(|| ApiError::bad_request("Missing file field"))?;
+        Ok(request)
+    }
+}
+
+#[derive(Serialize)]
+struct VerboseSegment<'a> {
+    id: usize,
+    start: f64,
+    end: f64,
+    text: &'a str,
+}
+
+#[derive(Serialize)]
+struct VerboseWord<'a> {
+    word: &'a str,
+    start: f64,
+    end: f64,
+}
+
+#[derive(Serialize)]
+struct VerboseResponse<'a> {
+    task: WhisperTask,
+    language: &'a str,
+    duration: f64,
+    text: &'a str,
+    segments: Vec<VerboseSegment<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    words: Option<Vec<VerboseWord<'a>>>,
+}
+
+async fn transcriptions(
+    State(state): State<HttpState>,
+    multipart: Multipart,
+) -> Result<Response, ApiError> {
+    process(state, multipart, WhisperTask::Transcribe).await
+}
+
+/// Translates the recording to English, like OpenAI's /v1/audio/translations
+async fn translations(
+    State(state): State<HttpState>,
+    multipart: Multipart,
+) -> Result<Response, ApiError> {
+    process(state, multipart, WhisperTask::Translate).await
+}
+
+async fn process(
+    state: HttpState,
+    multipart: Multipart,
+    task: WhisperTask,
+) -> Result<Response, ApiError> {
+    let request = TranscriptionRequest::from_multipart(multipart).await?;
+
+    let language = request
+        .language
+        .unwrap_or_else(|| state.config.whisper_language.clone());
+    if !is_supported_language(&language) {
+        return Err(ApiError::bad_request(format!(
+            "Unsupported language: {}",
+            language
+        )));
+    }
+
+    if let Some(model) = &request.model {
+        check_model(model, &state.config)?;
+    }
+
+    let samples = decode_wav(&request.file).map_err(ApiError::bad_request)?;
+
+    let options = DecodeOptions {
+        prompt: request
+            .prompt
+            .or_else(|| state.config.whisper_prompt.clone()),
+        context_tokens: state.config.context_tokens,
+        context: Some(Arc::new(SessionContext::default())),
+        temperature: request.temperature,
+        word_timestamps: request.word_timestamps,
+        language_candidates: state.config.language_candidates.clone(),
+        task,
+        include_source: false,
+        dictionary: state.config.dictionary.clone(),
+        include_raw: false,
+    };
+    let transcription = transcribe_recording(
+        &state.pool,
+        &state.vad,
+        &state.config,
+        samples,
+        language.clone(),
+        options,
+    )
+    .await
+    .map_err(|message| ApiError {
+        status: StatusCode::INTERNAL_SERVER_ERROR,
+        message,
+    })?;
+
+    Ok(render(
+        &transcription,
+        &language,
+        task,
+        request.response_format,
+        &state.config.subtitle_options(),
+    ))
+}
+
+/// Only the loaded model is available, by OpenAI's name or its file name without extension
+fn check_model(model: &str, config: &Config) -> Result<(), ApiError> {
+    let loaded = Path::new(&config.whisper_model_path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    if model == OPENAI_MODEL || model == loaded {
+        return Ok(());
+    }
+    Err(ApiError::bad_request(format!(
+        "Unsupported model: {} (available: {}, {})",
+        model, OPENAI_MODEL, loaded
+    )))
+}
+
+fn render(
+    transcription: &BatchTranscription,
+    language: &str,
+    task: WhisperTask,
+    format: ResponseFormat,
+    subtitles: &SubtitleOptions,
+) -> Response {
+    match format {
+        ResponseFormat::Json => Json(json!({ "text": transcription.text })).into_response(),
+        ResponseFormat::VerboseJson => {
+            let segments = transcription
+                .segments
+                .iter()
+                .enumerate()
+                .map(|(id, segment)| VerboseSegment {
+                    id,
+                    start: segment.start,
+                    end: segment.end,
+                    text: &segment.text,
+                })
+                .collect();
+            let words = transcription
+                .segments
+                .iter()
+                .any(|segment| segment.words.is_some())
+                .then(|| {
+                    transcription
+                        .segments
+                        .iter()
+                        .flat_map(|segment| segment.words.iter().flatten())
+                        .map(|word| VerboseWord {
+                            word: &word.text,
+                            start: word.start,
+                            end: word.end,
+                        })
+                        .collect()
+                });
+            Json(VerboseResponse {
+                task,
+                language: transcription.language.as_deref().unwrap_or(language),
+                duration: transcription.duration,
+                text: &transcription.text,
+                segments,
+                words,
+            })
+            .into_response()
+        }
+        ResponseFormat::Text => {
+            text_response("text/plain; charset=utf-8", transcription.text.clone())
+        }
+        ResponseFormat::Srt => text_response(
+            "text/plain; charset=utf-8",
+            to_srt(&transcription.segments, subtitles),
+        ),
+        ResponseFormat::Vtt => text_response(
+            "text/vtt; charset=utf-8",
+            to_vtt(&transcription.segments, subtitles),
+        ),
+    }
+}
+
+fn text_response(content_type: &'static str, body: String) -> Response {
+    ([(header::CONTENT_TYPE, content_type)], body).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::{Body, to_bytes};
+    use axum::extract::{FromRequest, Request};
+    use serde_json::Value;
+
+    use crate::protocol::{SegmentInfo, WordInfo};
+
+    const BOUNDARY: &str = "test-boundary";
+
+    /// Parses a form with the given text fields and a file
+    async fn request(fields: &[(&str, &str)]) -> Result<TranscriptionRequest, ApiError> {
+        let mut body = String::new();
+        for (name, value) in [("file", "RIFF")].iter().chain(fields) {
+            body.push_str(&format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                BOUNDARY, name, value
+            ));
+        }
+        body.push_str(&format!("--{}--\r\n", BOUNDARY));
+
+        let request = Request::builder()
+            .header(
+                header::CONTENT_TYPE,
+                format!("multipart/form-data; boundary={}", BOUNDARY),
+            )
+            .body(Body::from(body))
+            .unwrap();
+        let multipart = Multipart::from_request(request, &()).await.unwrap();
+        TranscriptionRequest::from_multipart(multipart).await
+    }
+
+    fn rejection(result: Result<TranscriptionRequest, ApiError>) -> String {
+        match result {
+            Ok(_) => panic!("request was accepted"),
+            Err(e) => {
+                assert_eq!(e.status, StatusCode::BAD_REQUEST);
+                e.message
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn form_fields_are_parsed() {
+        let parsed = request(&[
+            ("temperature", "0.4"),
+            ("response_format", "srt"),
+            ("timestamp_granularities[]", "word"),
+        ])
+        .await
+        .unwrap_or_else(|e| panic!("{}", e.message));
+
+        assert_eq!(parsed.file, b"RIFF");
+        assert_eq!(parsed.temperature, Some(0.4));
+        assert_eq!(parsed.response_format, ResponseFormat::Srt);
+        assert!(parsed.word_timestamps);
+    }
+
+    #[tokio::test]
+    async fn invalid_fields_are_rejected() {
+        assert!(rejection(request(&[("temperature", "1.5")]).await).contains("temperature"));
+        assert!(rejection(request(&[("temperature", "warm")]).await).contains("temperature"));
+        assert!(rejection(request(&[("response_format", "xml")]).await).contains("xml"));
+        assert!(
+            rejection(request(&[("timestamp_granularities[]", "sentence")]).await)
+                .contains("sentence")
+        );
+    }
+
+    #[test]
+    fn model_must_be_the_loaded_one() {
+        let config = Config {
+            whisper_model_path: "./models/ggml-small.bin".to_string(),
+            ..Config::default()
+        };
+        assert!(check_model("whisper-1", &config).is_ok());
+        assert!(check_model("ggml-small", &config).is_ok());
+
+        let error = check_model("gpt-4o-transcribe", &config).unwrap_err();
+        assert_eq!(error.status, StatusCode::BAD_REQUEST);
+        assert!(error.message.contains("ggml-small"), "{}", error.message);
+    }
+
+    #[tokio::test]
+    async fn verbose_json_lists_segments_and_words() {
+        let word = |text: &str, start, end| WordInfo {
+            text: text.to_string(),
+            start,
+            end,
+            probability: 0.9,
+        };
+        let transcription = BatchTranscription {
+            text: "Hello there. Bye.".to_string(),
+            segments: vec![
+                SegmentInfo {
+                    start: 0.5,
+                    end: 1.5,
+                    text: "Hello there.".to_string(),
+                    raw_text: None,
+                    words: Some(vec![word("Hello", 0.5, 1.0), word("there.", 1.0, 1.5)]),
+                    filter: None,
+                },
+                SegmentInfo {
+                    start: 2.0,
+                    end: 2.5,
+                    text: "Bye.".to_string(),
+                    raw_text: None,
+                    words: Some(vec![word("Bye.", 2.0, 2.5)]),
+                    filter: None,
+                },
+            ],
+            duration: 3.0,
+            language: Some("en".to_string()),
+        };
+
+        let response = render(
+            &transcription,
+            "auto",
+            WhisperTask::Transcribe,
+            ResponseFormat::VerboseJson,
+            &Config::default().subtitle_options(),
+        );
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let json: Value = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(json["task"], "transcribe");
+        // The detected language replaces auto
+        assert_eq!(json["language"], "en");
+        assert_eq!(json["duration"], 3.0);
+        assert_eq!(json["text"], "Hello there. Bye.");
+        assert_eq!(json["segments"][1]["id"], 1);
+        assert_eq!(json["segments"][1]["start"], 2.0);
+        assert_eq!(json["segments"][1]["text"], "Bye.");
+        assert_eq!(json["words"].as_array().unwrap().len(), 3);
+        assert_eq!(json["words"][1]["word"], "there.");
+        assert_eq!(json["words"][1]["end"], 1.5);
+    }
+}
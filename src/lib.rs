@@ -0,0 +1,34 @@
+//! Real-time speech recognition with a VAD and Whisper.
+//!
+//! [`TranscriptionPipeline`] runs one audio stream through the VAD and a shared
+//! [`WorkerPool`]; [`server::serve`] wires pipelines to WebSocket clients and the
+//! OpenAI-compatible HTTP API. Workers run a [`Transcriber`], which is Whisper in
+//! the server and may be a [`ScriptedTranscriber`] in tests.
+
+pub mod audio;
+pub mod batch;
+pub mod config;
+mod connection;
+pub mod context;
+pub mod dictionary;
+pub mod filter;
+mod http;
+pub mod ng;
+pub mod pipeline;
+pub mod protocol;
+pub mod server;
+pub mod session;
+pub mod subtitle;
+pub mod transcriber;
+pub mod vad;
+mod words;
+pub mod worker;
+
+pub use config::Config;
+pub use pipeline::TranscriptionPipeline;
+pub use protocol::ServerMessage;
+pub use transcriber::{ScriptedTranscriber, Transcriber};
+pub use vad::{
+    EnergyVadDetector, SileroVadDetector, SpeechSegment, VadFactory, VoiceActivityDetector,
+};
+pub use worker::WorkerPool;
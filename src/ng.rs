@@ -0,0 +1,442 @@
+use std::fs;
+use std::ops::Range;
+
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+/// How a rule's pattern is compared with the segment text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchKind {
+    /// The whole segment equals the pattern
+    #[default]
+    Exact,
+    /// The pattern appears anywhere in the segment
+    Substring,
+    /// The regular expression matches anywhere in the segment
+    Regex,
+}
+
+/// What a matching rule does to the segment
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NgAction {
+    /// Removes the whole segment
+    #[default]
+    Drop,
+    /// Removes the matched text
+    Delete,
+    /// Replaces each character of the matched text with `*`
+    Mask,
+}
+
+/// One entry of the rule file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleSpec {
+    pattern: String,
+    #[serde(default, rename = "match")]
+    kind: MatchKind,
+    #[serde(default)]
+    action: NgAction,
+    // Compare after NFKC, lowercasing and removing punctuation and spaces
+    #[serde(default = "default_normalize")]
+    normalize: bool,
+}
+
+fn default_normalize() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleFile {
+    #[serde(default)]
+    rules: Vec<RuleSpec>,
+}
+
+#[derive(Debug, Clone)]
+enum Matcher {
+    Exact(String),
+    Substring(String),
+    Regex(Regex),
+}
+
+#[derive(Debug, Clone)]
+struct NgRule {
+    matcher: Matcher,
+    action: NgAction,
+    normalize: bool,
+}
+
+/// NG word rules, applied to each segment in file order
+#[derive(Debug, Clone, Default)]
+pub struct NgRules {
+    rules: Vec<NgRule>,
+}
+
+impl NgRules {
+    /// Loads rules from a TOML file with `[[rules]]` entries
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read NG rules {}: {}", path, e))?;
+        Self::parse(&content).map_err(|e| format!("Invalid NG rules {}: {}", path, e))
+    }
+
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let file: RuleFile = toml::from_str(content).map_err(|e| e.to_string())?;
+        let mut rules = Self::default();
+        for spec in file.rules {
+            rules.push(spec)?;
+        }
+        Ok(rules)
+    }
+
+    /// Normalized exact-match drop rules, one per word
+    pub fn from_words(words: &[String]) -> Self {
+        let mut rules = Self::default();
+        for word in words {
+            // Words without letters or digits can not match normalized text
+            let _ = rules.push(RuleSpec {
+                pattern: word.clone(),
+                kind: MatchKind::Exact,
+                action: NgAction::Drop,
+                normalize: true,
+            });
+        }
+        rules
+    }
+
+    /// Appends the rules of `other`
+    pub fn extend(&mut self, other: NgRules) {
+        self.rules.extend(other.rules);
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    fn push(&mut self, spec: RuleSpec) -> Result<(), String> {
+        let pattern = if spec.normalize && spec.kind != MatchKind::Regex {
+            normalize(&spec.pattern).text
+        } else {
+            spec.pattern.clone()
+        };
+        if pattern.is_empty() {
+            return Err(format!("pattern {:?} matches nothing", spec.pattern));
+        }
+
+        let matcher = match spec.kind {
+            MatchKind::Exact => Matcher::Exact(pattern),
+            MatchKind::Substring => Matcher::Substring(pattern),
+            MatchKind::Regex => Matcher::Regex(
+                RegexBuilder::new(&pattern)
+                    // Normalized text is lowercase
+                    .case_insensitive(spec.normalize)
+                    .build()
+                    .map_err(|e| format!("pattern {:?}: {}", spec.pattern, e))?,
+            ),
+        };
+        self.rules.push(NgRule {
+            matcher,
+            action: spec.action,
+            normalize: spec.normalize,
+        });
+        Ok(())
+    }
+
+    /// Text of the segment after all rules, or `None` when it is dropped.
+    /// A segment that deletions leave with no letters or digits is dropped too.
+    pub fn apply(&self, text: &str) -> Option<String> {
+        let mut text = text.to_string();
+        let mut edited = false;
+
+        for rule in &self.rules {
+            let matches = rule.find(&text);
+            if matches.is_empty() {
+                continue;
+            }
+
+            edited = true;
+            text = match rule.action {
+                NgAction::Drop => return None,
+                NgAction::Delete => {
+                    let deleted = replace(&text, &matches, |_| String::new());
+                    deleted.split_whitespace().collect::<Vec<_>>().join(" ")
+                }
+                NgAction::Mask => replace(&text, &matches, |matched| {
+                    "*".repeat(matched.chars().count())
+                }),
+            };
+        }
+
+        // Segments no rule touched are kept as they are, even "♪" or "……"
+        let has_content = text.chars().any(|c| c.is_alphanumeric() || c == '*');
+        (!edited || has_content).then_some(text)
+    }
+}
+
+impl NgRule {
+    /// Byte ranges of the original text matched by the rule, in order and without overlaps
+    fn find(&self, text: &str) -> Vec<Range<usize>> {
+        if !self.normalize {
+            return match &self.matcher {
+                Matcher::Exact(pattern) if text.trim() == pattern => {
+                    std::iter::once(0..text.len()).collect()
+                }
+                Matcher::Exact(_) => Vec::new(),
+                Matcher::Substring(pattern) => text
+                    .match_indices(pattern.as_str())
+                    .map(|(start, matched)| start..start + matched.len())
+                    .collect(),
+                Matcher::Regex(regex) => regex
+                    .find_iter(text)
+                    .filter(|m| !m.is_empty())
+                    .map(|m| m.range())
+                    .collect(),
+            };
+        }
+
+        let normalized = normalize(text);
+        let ranges: Vec<Range<usize>> = match &self.matcher {
+            Matcher::Exact(pattern) if normalized.text == *pattern => {
+                std::iter::once(0..pattern.len()).collect()
+            }
+            Matcher::Exact(_) => Vec::new(),
+            Matcher::Substring(pattern) => normalized
+                .text
+                .match_indices(pattern.as_str())
+                .map(|(start, matched)| start..start + matched.len())
+                .collect(),
+            Matcher::Regex(regex) => regex
+                .find_iter(&normalized.text)
+                .filter(|m| !m.is_empty())
+                .map(|m| m.range())
+                .collect(),
+        };
+
+        // Matches inside one original character (e.g. "㍻" -> "平成") may touch the same range
+        let mut original: Vec<Range<usize>> = Vec::new();
+        for range in ranges {
+            let range = normalized.original_range(range);
+            match original.last_mut() {
+                Some(last) if range.start < last.end => last.end = last.end.max(range.end),
+                _ => original.push(range),
+            }
+        }
+        original
+    }
+}
+
+/// Rebuilds the text with each matched range replaced
+fn replace(text: &str, ranges: &[Range<usize>], with: impl Fn(&str) -> String) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut position = 0;
+    for range in ranges {
+        output.push_str(&text[position..range.start]);
+        output.push_str(&with(&text[range.clone()]));
+        position = range.end;
+    }
+    output.push_str(&text[position..]);
+    output
+}
+
+/// Comparison form of a text, remembering where each character came from
+struct Normalized {
+    text: String,
+    // Byte offset in `text` of each character and the byte range it came from
+    origins: Vec<(usize, Range<usize>)>,
+}
+
+impl Normalized {
+    /// Maps a byte range of the normalized text back to the original text
+    fn original_range(&self, range: Range<usize>) -> Range<usize> {
+        let first = self
+            .origins
+            .partition_point(|(offset, _)| *offset < range.start);
+        let last = self
+            .origins
+            .partition_point(|(offset, _)| *offset < range.end)
+            - 1;
+        self.origins[first].1.start..self.origins[last].1.end
+    }
+}
+
+/// NFKC, lowercase, letters and digits only.
+/// Combining marks are normalized together with the character before them, so that
+/// half-width "ｶﾞ" becomes "ガ".
+fn normalize(text: &str) -> Normalized {
+    let mut clusters: Vec<Range<usize>> = Vec::new();
+    for (index, c) in text.char_indices() {
+        let range = index..index + c.len_utf8();
+        let combining = std::iter::once(c)
+            .nfkc()
+            .next()
+            .is_some_and(is_combining_mark);
+        match clusters.last_mut() {
+            Some(last) if combining => last.end = range.end,
+            _ => clusters.push(range),
+        }
+    }
+
+    let mut normalized = Normalized {
+        text: String::new(),
+        origins: Vec::new(),
+    };
+    for cluster in clusters {
+        for c in text[cluster.clone()].nfkc().flat_map(char::to_lowercase) {
+            if c.is_alphanumeric() {
+                normalized
+                    .origins
+                    .push((normalized.text.len(), cluster.clone()));
+                normalized.text.push(c);
+            }
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(content: &str) -> NgRules {
+        NgRules::parse(content).unwrap()
+    }
+
+    #[test]
+    fn exact_rules_ignore_punctuation_width_and_case() {
+        let rules = rules(
+            r#"
+            [[rules]]
+            pattern = "ご視聴ありがとうございました"
+
+            [[rules]]
+            pattern = "バイバイ"
+
+            [[rules]]
+            pattern = "Thank you for watching"
+            "#,
+        );
+
+        assert_eq!(rules.apply("ご視聴ありがとうございました。"), None);
+        assert_eq!(rules.apply("ご視聴、ありがとうございました！"), None);
+        // Half-width kana with a separate voiced sound mark
+        assert_eq!(rules.apply("ﾊﾞｲﾊﾞｲ！"), None);
+        assert_eq!(rules.apply("THANK YOU FOR WATCHING!"), None);
+        assert_eq!(
+            rules.apply("Ｔｈａｎｋ ｙｏｕ ｆｏｒ ｗａｔｃｈｉｎｇ."),
+            None
+        );
+        assert_eq!(
+            rules.apply("ご視聴ありがとうございました、また明日"),
+            Some("ご視聴ありがとうございました、また明日".to_string())
+        );
+    }
+
+    #[test]
+    fn substring_rules_delete_or_mask_the_original_text() {
+        let rules = rules(
+            r#"
+            [[rules]]
+            pattern = "えーと"
+            match = "substring"
+            action = "delete"
+
+            [[rules]]
+            pattern = "damn"
+            match = "substring"
+            action = "mask"
+
+            [[rules]]
+            pattern = "秘密"
+            match = "substring"
+            action = "mask"
+            "#,
+        );
+
+        assert_eq!(
+            rules.apply("えーと 今日は晴れです"),
+            Some("今日は晴れです".to_string())
+        );
+        assert_eq!(
+            rules.apply("Well, DAMN it."),
+            Some("Well, **** it.".to_string())
+        );
+        // Punctuation inside the match is covered too
+        assert_eq!(
+            rules.apply("これは秘・密です"),
+            Some("これは***です".to_string())
+        );
+        // Nothing but filler is left
+        assert_eq!(rules.apply("えーと、"), None);
+    }
+
+    #[test]
+    fn punctuation_only_segments_pass_when_no_rule_matches() {
+        let rules = rules(
+            r#"
+            [[rules]]
+            pattern = "えーと"
+            match = "substring"
+            action = "delete"
+            "#,
+        );
+
+        for text in ["……", "？", "♪"] {
+            assert_eq!(rules.apply(text), Some(text.to_string()));
+        }
+        assert_eq!(
+            NgRules::from_words(&["あ".to_string()]).apply("♪～"),
+            Some("♪～".to_string())
+        );
+    }
+
+    #[test]
+    fn regex_rules_match_normalized_or_raw_text() {
+        let rules = rules(
+            r#"
+            [[rules]]
+            pattern = "(はい){3,}"
+            match = "regex"
+
+            [[rules]]
+            pattern = '\b\d{3}-\d{4}-\d{4}\b'
+            match = "regex"
+            action = "mask"
+            normalize = false
+            "#,
+        );
+
+        assert_eq!(rules.apply("はい、はい、はい。"), None);
+        assert_eq!(
+            rules.apply("はい、そうです"),
+            Some("はい、そうです".to_string())
+        );
+        assert_eq!(
+            rules.apply("Call 090-1234-5678 now"),
+            Some("Call ************* now".to_string())
+        );
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        assert!(NgRules::parse("[[rules]]\npattern = \"。\"").is_err());
+        assert!(NgRules::parse("[[rules]]\npattern = \"(\"\nmatch = \"regex\"").is_err());
+        assert!(NgRules::parse("[[rules]]\npattern = \"a\"\naction = \"hide\"").is_err());
+    }
+
+    #[test]
+    fn words_become_exact_drop_rules() {
+        let rules = NgRules::from_words(&["あ".to_string(), "、".to_string()]);
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules.apply("あ。"), None);
+        assert_eq!(rules.apply("あい"), Some("あい".to_string()));
+    }
+}
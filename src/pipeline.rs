@@ -0,0 +1,558 @@
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, mpsc, oneshot};
+
+use crate::audio::{AudioDecoder, PIPELINE_SAMPLE_RATE};
+use crate::config::Config;
+use crate::context::SessionContext;
+use crate::protocol::{SegmentInfo, ServerMessage, SessionSettings, round_time};
+use crate::session::SessionConfig;
+use crate::subtitle::{self, SubtitleFormat, SubtitleOptions};
+use crate::transcriber::CancelToken;
+use crate::vad::{SpeechSegment, VadEvent, VadFactory, VoiceActivityDetector};
+use crate::worker::{DecodeOptions, Task, TaskKind, WorkerPool};
+
+// Messages and pending results queued for delivery
+const OUTBOUND_BUFFER: usize = 64;
+// Messages buffered before the events receiver
+const EVENT_BUFFER: usize = 64;
+
+/// Audio of a stream that the VAD may still cut an utterance from
+#[derive(Default)]
+struct StreamAudio {
+    samples: Vec<f32>,
+    // Absolute sample offset of samples[0]
+    offset: usize,
+    // Total number of samples received on this stream
+    received: usize,
+}
+
+impl StreamAudio {
+    fn push(&mut self, chunk: &[f32]) {
+        self.samples.extend_from_slice(chunk);
+        self.received += chunk.len();
+    }
+
+    fn extract(&self, segment: &SpeechSegment) -> Vec<f32> {
+        self.range(segment.start_offset, segment.end_offset)
+    }
+
+    /// Copies the samples between two absolute offsets that are still buffered
+    fn range(&self, start: usize, end: usize) -> Vec<f32> {
+        let start = start.saturating_sub(self.offset).min(self.samples.len());
+        let end = end.saturating_sub(self.offset).min(self.samples.len());
+        self.samples[start..end.max(start)].to_vec()
+    }
+
+    fn discard_before(&mut self, offset: usize) {
+        let count = offset.saturating_sub(self.offset).min(self.samples.len());
+        self.samples.drain(..count);
+        self.offset += count;
+    }
+}
+
+/// Entries of the outbound queue, delivered strictly in the order they were queued
+enum Outbound {
+    Message(ServerMessage),
+    /// A transcription still running.
+    /// Holds one of the stream's in-flight slots until the result is out.
+    Pending {
+        result: oneshot::Receiver<ServerMessage>,
+        permit: OwnedSemaphorePermit,
+    },
+    /// Renders the transcript of every result queued before it
+    Export {
+        format: SubtitleFormat,
+        options: SubtitleOptions,
+    },
+}
+
+/// Utterance the VAD is still inside of, tracked for partial results
+struct OpenUtterance {
+    // Absolute sample offset where the speech started
+    start: usize,
+    // Assigned with the first partial, and reused by the final result
+    seq: Option<u64>,
+    // Stream sample count when the last partial was requested
+    last_partial: usize,
+    // Cancels the partial that is queued or running
+    partial: Option<CancelToken>,
+}
+
+/// VAD and Whisper for one audio stream.
+///
+/// Audio pushed in is cut into utterances at detected silences and transcribed on the
+/// shared worker pool. Results come out of the events receiver returned by
+/// [`TranscriptionPipeline::new`] in utterance order, as the same messages the
+/// WebSocket server sends. The receiver ends once the pipeline is dropped and every
+/// queued result is out.
+pub struct TranscriptionPipeline {
+    // Replaced by `apply_config`
+    config: Config,
+    pool: Arc<WorkerPool>,
+    // Shared VAD model; `apply_config` creates a new detector from it
+    vad_factory: VadFactory,
+    // Runs on the blocking pool, see `run_vad`
+    vad: Arc<Mutex<Box<dyn VoiceActivityDetector>>>,
+    decoder: AudioDecoder,
+    audio: StreamAudio,
+    // Limits the utterances of this stream queued or running at once
+    in_flight: Arc<Semaphore>,
+    out_tx: mpsc::Sender<Outbound>,
+    // Sequence number of the next utterance sent to Whisper
+    next_seq: u64,
+    // Cancels this stream's queued and running transcriptions
+    cancel: CancelToken,
+    utterance: Option<OpenUtterance>,
+    // Allows one partial per stream at a time
+    partial_slot: Arc<Semaphore>,
+    // Recent final text, used as the prompt of later utterances
+    context: Arc<SessionContext>,
+}
+
+impl TranscriptionPipeline {
+    /// Creates the pipeline and the receiver of its results.
+    /// Must be called inside a Tokio runtime.
+    pub fn new(
+        config: Config,
+        pool: Arc<WorkerPool>,
+        vad_factory: VadFactory,
+    ) -> Result<(Self, mpsc::Receiver<ServerMessage>), String> {
+        let vad = vad_factory
+            .create(&config)
+            .map_err(|e| format!("VAD initialization failed: {}", e))?;
+        let (out_tx, out_rx) = mpsc::channel(OUTBOUND_BUFFER);
+        let (event_tx, event_rx) = mpsc::channel(EVENT_BUFFER);
+        tokio::spawn(deliver(out_rx, event_tx));
+
+        let vad = Arc::new(Mutex::new(vad));
+        let pipeline = Self {
+            in_flight: Arc::new(Semaphore::new(config.max_in_flight.max(1))),
+            decoder: AudioDecoder::new(config.audio_format()),
+            config,
+            pool,
+            vad_factory,
+            vad,
+            audio: StreamAudio::default(),
+            out_tx,
+            next_seq: 0,
+            cancel: CancelToken::default(),
+            utterance: None,
+            partial_slot: Arc::new(Semaphore::new(1)),
+            context: Arc::new(SessionContext::default()),
+        };
+        Ok((pipeline, event_rx))
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Samples received so far, at 16 kHz
+    pub fn received_samples(&self) -> usize {
+        self.audio.received
+    }
+
+    /// Queues a message behind the results of the audio pushed so far
+    pub async fn send(&self, message: ServerMessage) {
+        let _ = self.out_tx.send(Outbound::Message(message)).await;
+    }
+
+    /// Pushes audio frames in the configured encoding, sample rate and channels.
+    /// A frame that does not hold a whole number of samples is rejected.
+    pub async fn push_audio(&mut self, data: &[u8]) -> Result<(), String> {
+        let samples = self.decoder.decode(data)?;
+        self.push_samples(&samples).await;
+        Ok(())
+    }
+
+    /// Pushes 16 kHz mono samples, queueing every utterance the VAD finishes.
+    /// Waits while `max_in_flight` utterances of this stream are queued or running.
+    pub async fn push_samples(&mut self, samples: &[f32]) {
+        if samples.is_empty() {
+            return;
+        }
+
+        self.audio.push(samples);
+
+        // Run the streaming VAD; utterances are cut at detected silences
+        let chunk = samples.to_vec();
+        match self.run_vad(move |vad| vad.process_chunk(&chunk)).await {
+            Ok(events) => self.process_vad_events(events).await,
+            Err(e) => {
+                eprintln!("❌ VAD error: {}", e);
+            }
+        }
+
+        self.request_partial().await;
+
+        self.discard_audio();
+    }
+
+    /// Closes any open utterance and processes it
+    pub async fn flush(&mut self) {
+        let tail = self.decoder.flush();
+        self.push_samples(&tail).await;
+        println!("🔄 Flushing remaining {} samples", self.audio.samples.len());
+
+        match self.run_vad(|vad| vad.finish()).await {
+            Ok(events) => self.process_vad_events(events).await,
+            Err(e) => {
+                eprintln!("❌ VAD error on flush: {}", e);
+            }
+        }
+
+        self.discard_audio();
+    }
+
+    /// Applies session overrides before any audio, returning the resulting settings
+    pub fn apply_config(
+        &mut self,
+        session_config: &SessionConfig,
+    ) -> Result<SessionSettings, String> {
+        if self.audio.received > 0 {
+            return Err("config must be sent before any audio".to_string());
+        }
+
+        let config = session_config.apply(&self.config)?;
+        if let Some(name) = &config.dictionary
+            && !self.pool.dictionaries().contains(name)
+        {
+            return Err(format!(
+                "Unknown dictionary: {} (available: {})",
+                name,
+                self.pool.dictionaries().names().join(", ")
+            ));
+        }
+        let vad = self
+            .vad_factory
+            .create(&config)
+            .map_err(|e| format!("VAD initialization failed: {}", e))?;
+
+        self.config = config;
+        self.vad = Arc::new(Mutex::new(vad));
+        self.decoder = AudioDecoder::new(self.config.audio_format());
+        println!(
+            "⚙️  Session config applied (language: {}, audio: {} {}Hz {}ch, VAD threshold: {}, block: {}s)",
+            self.config.whisper_language,
+            self.config.encoding,
+            self.config.sample_rate,
+            self.config.channels,
+            self.config.vad_threshold,
+            self.config.whisper_block_seconds
+        );
+        Ok(SessionSettings::from(&self.config))
+    }
+
+    /// Queues a subtitle export of every result queued before it
+    pub async fn export(&self, format: SubtitleFormat, options: SubtitleOptions) {
+        let _ = self.out_tx.send(Outbound::Export { format, options }).await;
+    }
+
+    /// Processes the trailing audio; the results are still delivered
+    pub async fn finish(mut self) {
+        self.cancel_partial();
+        self.flush().await;
+    }
+
+    /// Drops the trailing audio and cancels every queued or running transcription
+    pub fn cancel(self) {
+        self.cancel_partial();
+        if !self.audio.samples.is_empty() {
+            println!("🗑️  Dropping {} trailing samples", self.audio.samples.len());
+        }
+        self.cancel.cancel();
+    }
+
+    /// Runs a VAD step on the blocking pool.
+    /// Silero waits there for the shared inference thread, never on a runtime thread.
+    async fn run_vad<F>(&self, step: F) -> Result<Vec<VadEvent>, String>
+    where
+        F: FnOnce(
+                &mut dyn VoiceActivityDetector,
+            ) -> Result<Vec<VadEvent>, Box<dyn std::error::Error>>
+            + Send
+            + 'static,
+    {
+        let vad = self.vad.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut vad = vad.lock().unwrap();
+            step(vad.as_mut()).map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| format!("VAD task failed: {}", e))?
+    }
+
+    /// Drops the audio the VAD can no longer cut an utterance from
+    fn discard_audio(&mut self) {
+        let offset = self.vad.lock().unwrap().retain_offset();
+        self.audio.discard_before(offset);
+    }
+
+    /// Nobody will see an interim result any more
+    fn cancel_partial(&self) {
+        if let Some(cancel) = self.utterance.as_ref().and_then(|u| u.partial.as_ref()) {
+            cancel.cancel();
+        }
+    }
+
+    /// Submits each finished utterance to the worker pool.
+    /// Results are not awaited here; the delivery task sends them in order.
+    async fn process_vad_events(&mut self, events: Vec<VadEvent>) {
+        for event in events {
+            let segment = match event {
+                VadEvent::SpeechStart { offset } => {
+                    // An utterance the detector never ended gets no final result
+                    if let Some(previous) = self.utterance.take() {
+                        if let Some(cancel) = &previous.partial {
+                            cancel.cancel();
+                        }
+                        if let Some(seq) = previous.seq {
+                            let start = previous.start as f64 / PIPELINE_SAMPLE_RATE as f64;
+                            let end =
+                                offset.max(previous.start) as f64 / PIPELINE_SAMPLE_RATE as f64;
+                            self.clear_partials(seq, start, end - start).await;
+                        }
+                    }
+
+                    println!(
+                        "🎯 Speech started at {:.2}s",
+                        offset as f64 / PIPELINE_SAMPLE_RATE as f64
+                    );
+                    self.utterance = Some(OpenUtterance {
+                        start: offset,
+                        seq: None,
+                        last_partial: offset,
+                        partial: None,
+                    });
+                    continue;
+                }
+                VadEvent::SpeechEnd(segment) => segment,
+            };
+
+            // A partial still waiting would be stale once the final is queued
+            let utterance = self.utterance.take();
+            if let Some(cancel) = utterance.as_ref().and_then(|u| u.partial.as_ref()) {
+                cancel.cancel();
+            }
+            let partial_seq = utterance.and_then(|u| u.seq);
+
+            let speech_audio = self.audio.extract(&segment);
+            let start_time = segment.start_offset as f64 / PIPELINE_SAMPLE_RATE as f64;
+
+            println!(
+                "  📢 Segment: {:.2}s - {:.2}s ({} samples)",
+                segment.start_second,
+                segment.end_second,
+                speech_audio.len()
+            );
+
+            // Only process if meets minimum length
+            if speech_audio.len() < self.config.min_speech_samples {
+                println!(
+                    "  ⚠️  Segment too short, skipping ({} < {})",
+                    speech_audio.len(),
+                    self.config.min_speech_samples
+                );
+
+                if let Some(seq) = partial_seq {
+                    let duration = speech_audio.len() as f64 / PIPELINE_SAMPLE_RATE as f64;
+                    self.clear_partials(seq, start_time, duration).await;
+                }
+                continue;
+            }
+
+            // Wait for a free slot; the caller keeps buffering audio meanwhile
+            let permit = match self.in_flight.clone().try_acquire_owned() {
+                Ok(permit) => permit,
+                Err(_) => {
+                    println!(
+                        "  ⏳ {} utterances in flight, waiting for a result",
+                        self.config.max_in_flight
+                    );
+                    match self.in_flight.clone().acquire_owned().await {
+                        Ok(permit) => permit,
+                        Err(_) => return,
+                    }
+                }
+            };
+
+            let seq = match partial_seq {
+                Some(seq) => seq,
+                None => self.allocate_seq(),
+            };
+            let kind = if self.config.partial_interval_ms > 0 {
+                TaskKind::Final
+            } else {
+                TaskKind::Transcription
+            };
+
+            // Queue the placeholder before submitting so results keep utterance order
+            let (result_tx, result_rx) = oneshot::channel();
+            let pending = Outbound::Pending {
+                result: result_rx,
+                permit,
+            };
+            if self.out_tx.send(pending).await.is_err() {
+                return;
+            }
+
+            let depth = self.pool.submit(Task {
+                seq,
+                kind,
+                audio_data: speech_audio,
+                language: self.config.whisper_language.clone(),
+                start_time,
+                options: self.decode_options(),
+                responder: result_tx,
+                cancel: self.cancel.clone(),
+            });
+            println!(
+                "  📥 Queued utterance #{} for transcription (queue depth {})",
+                seq, depth
+            );
+        }
+    }
+
+    /// Clears the partials the client already shows for an utterance with no result
+    async fn clear_partials(&self, seq: u64, start_time: f64, duration: f64) {
+        self.send(ServerMessage::Final {
+            seq,
+            transcription: String::new(),
+            raw_transcription: None,
+            task: None,
+            source: None,
+            detected_language: None,
+            segments: Vec::new(),
+            filtered: Vec::new(),
+            duration: round_time(duration),
+            start: round_time(start_time),
+            end: round_time(start_time + duration),
+        })
+        .await;
+    }
+
+    fn decode_options(&self) -> DecodeOptions {
+        DecodeOptions {
+            word_timestamps: self.config.word_timestamps,
+            language_candidates: self.config.language_candidates.clone(),
+            task: self.config.whisper_task,
+            include_source: self.config.include_source,
+            prompt: self.config.whisper_prompt.clone(),
+            context_tokens: self.config.context_tokens,
+            context: Some(self.context.clone()),
+            dictionary: self.config.dictionary.clone(),
+            include_raw: self.config.include_raw,
+            ..DecodeOptions::default()
+        }
+    }
+
+    fn allocate_seq(&mut self) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        seq
+    }
+
+    /// Re-transcribes the open utterance once `partial_interval_ms` of new audio arrived.
+    /// Skipped while the previous partial of this stream is still queued or running.
+    async fn request_partial(&mut self) {
+        let interval =
+            self.config.partial_interval_ms as usize * PIPELINE_SAMPLE_RATE as usize / 1000;
+        let received = self.audio.received;
+        let min_speech_samples = self.config.min_speech_samples;
+
+        let Some(utterance) = self.utterance.as_mut() else {
+            return;
+        };
+        if interval == 0
+            || received - utterance.last_partial < interval
+            || received - utterance.start < min_speech_samples
+        {
+            return;
+        }
+
+        let Ok(permit) = self.partial_slot.clone().try_acquire_owned() else {
+            return;
+        };
+
+        let seq = match utterance.seq {
+            Some(seq) => seq,
+            None => {
+                let seq = self.next_seq;
+                self.next_seq += 1;
+                utterance.seq = Some(seq);
+                seq
+            }
+        };
+        let cancel = CancelToken::default();
+        utterance.last_partial = received;
+        utterance.partial = Some(cancel.clone());
+        let start = utterance.start;
+
+        let (result_tx, result_rx) = oneshot::channel();
+        let pending = Outbound::Pending {
+            result: result_rx,
+            permit,
+        };
+        if self.out_tx.send(pending).await.is_err() {
+            return;
+        }
+
+        let depth = self.pool.submit(Task {
+            seq,
+            kind: TaskKind::Partial,
+            audio_data: self.audio.range(start, received),
+            language: self.config.whisper_language.clone(),
+            start_time: start as f64 / PIPELINE_SAMPLE_RATE as f64,
+            options: self.decode_options(),
+            responder: result_tx,
+            cancel,
+        });
+        println!(
+            "  📝 Queued partial #{} ({:.2}s of speech, queue depth {})",
+            seq,
+            (received - start) as f64 / PIPELINE_SAMPLE_RATE as f64,
+            depth
+        );
+    }
+}
+
+/// Sends queued messages to the events receiver in order, waiting for pending
+/// transcriptions in turn. Keeps the transcript of the stream for subtitle exports.
+/// Once the receiver is gone, the remaining results are only awaited to free the slots.
+async fn deliver(mut out_rx: mpsc::Receiver<Outbound>, event_tx: mpsc::Sender<ServerMessage>) {
+    let mut transcript: Vec<SegmentInfo> = Vec::new();
+    while let Some(item) = out_rx.recv().await {
+        let message = match item {
+            Outbound::Message(message) => message,
+            Outbound::Pending { result, permit } => {
+                let result = result.await;
+                drop(permit);
+                match result {
+                    Ok(message) => message,
+                    // The worker dropped the task without a result
+                    Err(_) => continue,
+                }
+            }
+            Outbound::Export { format, options } => {
+                println!(
+                    "🎬 Exporting {} transcript segments as {:?}",
+                    transcript.len(),
+                    format
+                );
+                ServerMessage::Subtitle {
+                    format,
+                    content: subtitle::render(&transcript, format, &options),
+                }
+            }
+        };
+
+        if let ServerMessage::Transcription { segments, .. }
+        | ServerMessage::Final { segments, .. } = &message
+        {
+            transcript.extend(segments.iter().cloned());
+        }
+
+        let _ = event_tx.send(message).await;
+    }
+}
@@ -0,0 +1,589 @@
+use serde::Serialize;
+
+use crate::audio::Encoding;
+use crate::config::Config;
+use crate::filter::FilterReason;
+use crate::subtitle::SubtitleFormat;
+use crate::worker::WhisperTask;
+
+/// Version of the JSON wire format, sent with every server message
+pub const PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SegmentInfo {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+    // Text before dictionary replacements, when requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_text: Option<String>,
+    // Only present when word timestamps are enabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub words: Option<Vec<WordInfo>>,
+    // Hallucination filter the segment failed, when filters only flag
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<FilterReason>,
+}
+
+/// Segment removed by an NG word or hallucination filter
+#[derive(Debug, Clone, Serialize)]
+pub struct FilteredSegment {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+    pub reason: FilterReason,
+}
+
+/// Source-language transcript of a translated utterance
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceTranscript {
+    pub transcription: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_transcription: Option<String>,
+    pub segments: Vec<SegmentInfo>,
+}
+
+/// Language Whisper detected for an utterance in `auto` mode
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DetectedLanguage {
+    pub language: String,
+    pub language_probability: f32,
+}
+
+/// Word of a segment with its timing and the mean probability of its tokens
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WordInfo {
+    pub text: String,
+    pub start: f64,
+    pub end: f64,
+    pub probability: f32,
+}
+
+/// Effective settings of a session, reported after a config message
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionSettings {
+    pub language: String,
+    pub language_candidates: Vec<String>,
+    pub task: WhisperTask,
+    pub include_source: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    pub context_tokens: usize,
+    pub vad_threshold: f32,
+    pub vad_min_speech_duration_ms: i32,
+    pub vad_max_speech_duration_seconds: f32,
+    pub vad_min_silence_duration_ms: i32,
+    pub vad_speech_pad_ms: i32,
+    pub block_seconds: usize,
+    pub min_speech_samples: usize,
+    pub partial_interval_ms: u64,
+    pub word_timestamps: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dictionary: Option<String>,
+    pub include_raw: bool,
+    pub encoding: Encoding,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl From<&Config> for SessionSettings {
+    fn from(config: &Config) -> Self {
+        Self {
+            language: config.whisper_language.clone(),
+            language_candidates: config.language_candidates.clone(),
+            task: config.whisper_task,
+            include_source: config.include_source,
+            prompt: config.whisper_prompt.clone(),
+            context_tokens: config.context_tokens,
+            vad_threshold: config.vad_threshold,
+            vad_min_speech_duration_ms: config.vad_min_speech_duration_ms,
+            vad_max_speech_duration_seconds: config.vad_max_speech_duration_seconds,
+            vad_min_silence_duration_ms: config.vad_min_silence_duration_ms,
+            vad_speech_pad_ms: config.vad_speech_pad_ms,
+            block_seconds: config.whisper_block_seconds,
+            min_speech_samples: config.min_speech_samples,
+            partial_interval_ms: config.partial_interval_ms,
+            word_timestamps: config.word_timestamps,
+            dictionary: config.dictionary.clone(),
+            include_raw: config.include_raw,
+            encoding: config.encoding,
+            sample_rate: config.sample_rate,
+            channels: config.channels,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Ready,
+    ConfigApplied,
+}
+
+/// Messages sent from the server to the client
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Transcription {
+        seq: u64,
+        transcription: String,
+        // Text before dictionary replacements, when requested
+        #[serde(skip_serializing_if = "Option::is_none")]
+        raw_transcription: Option<String>,
+        // Set in translate mode, where the text is an English translation
+        #[serde(skip_serializing_if = "Option::is_none")]
+        task: Option<WhisperTask>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        source: Option<Box<SourceTranscript>>,
+        #[serde(flatten)]
+        detected_language: Option<DetectedLanguage>,
+        segments: Vec<SegmentInfo>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        filtered: Vec<FilteredSegment>,
+        duration: f64,
+        start: f64,
+        end: f64,
+    },
+    /// Interim result for an utterance that is still being spoken
+    Partial {
+        seq: u64,
+        transcription: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        raw_transcription: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        task: Option<WhisperTask>,
+        #[serde(flatten)]
+        detected_language: Option<DetectedLanguage>,
+        duration: f64,
+        start: f64,
+        end: f64,
+    },
+    /// Complete utterance of a session with partial results; replaces its partials
+    Final {
+        seq: u64,
+        transcription: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        raw_transcription: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        task: Option<WhisperTask>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        source: Option<Box<SourceTranscript>>,
+        #[serde(flatten)]
+        detected_language: Option<DetectedLanguage>,
+        segments: Vec<SegmentInfo>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        filtered: Vec<FilteredSegment>,
+        duration: f64,
+        start: f64,
+        end: f64,
+    },
+    NoSpeech {
+        seq: u64,
+        // Segments that were heard but filtered out
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        filtered: Vec<FilteredSegment>,
+        duration: f64,
+        start: f64,
+        end: f64,
+    },
+    Error {
+        // Set when the error belongs to a transcribed utterance
+        #[serde(skip_serializing_if = "Option::is_none")]
+        seq: Option<u64>,
+        message: String,
+    },
+    Status {
+        status: Status,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        config: Option<SessionSettings>,
+    },
+    /// Transcript of the session so far as a subtitle document
+    Subtitle {
+        format: SubtitleFormat,
+        content: String,
+    },
+}
+
+#[derive(Serialize)]
+struct Envelope<'a> {
+    version: u32,
+    #[serde(flatten)]
+    message: &'a ServerMessage,
+}
+
+impl ServerMessage {
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::Error {
+            seq: None,
+            message: message.into(),
+        }
+    }
+
+    pub fn status(status: Status) -> Self {
+        Self::Status {
+            status,
+            config: None,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let envelope = Envelope {
+            version: PROTOCOL_VERSION,
+            message: self,
+        };
+        serde_json::to_string(&envelope).expect("server messages always serialize")
+    }
+}
+
+/// Rounds a time in seconds to centiseconds, the resolution of Whisper timestamps
+pub fn round_time(seconds: f64) -> f64 {
+    (seconds * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transcription_wire_format() {
+        let message = ServerMessage::Transcription {
+            seq: 3,
+            transcription: "こんにちは 世界".to_string(),
+            raw_transcription: None,
+            task: None,
+            source: None,
+            detected_language: None,
+            segments: vec![
+                SegmentInfo {
+                    start: 12.4,
+                    end: 13.6,
+                    text: "こんにちは".to_string(),
+                    raw_text: None,
+                    words: None,
+                    filter: None,
+                },
+                SegmentInfo {
+                    start: 13.7,
+                    end: 15.2,
+                    text: "世界".to_string(),
+                    raw_text: None,
+                    words: None,
+                    filter: None,
+                },
+            ],
+            filtered: vec![],
+            duration: 3.0,
+            start: 12.4,
+            end: 15.4,
+        };
+
+        assert_eq!(
+            message.to_json(),
+            r#"{"version":1,"type":"transcription","seq":3,"transcription":"こんにちは 世界","segments":[{"start":12.4,"end":13.6,"text":"こんにちは"},{"start":13.7,"end":15.2,"text":"世界"}],"duration":3.0,"start":12.4,"end":15.4}"#
+        );
+    }
+
+    #[test]
+    fn transcription_escapes_control_characters() {
+        let message = ServerMessage::Transcription {
+            seq: 0,
+            transcription: "a\"b\\c\td\ne\u{1}".to_string(),
+            raw_transcription: None,
+            task: None,
+            source: None,
+            detected_language: None,
+            segments: vec![],
+            filtered: vec![],
+            duration: 1.0,
+            start: 0.0,
+            end: 1.0,
+        };
+
+        let json = message.to_json();
+        assert_eq!(
+            json,
+            r#"{"version":1,"type":"transcription","seq":0,"transcription":"a\"b\\c\td\ne\u0001","segments":[],"duration":1.0,"start":0.0,"end":1.0}"#
+        );
+
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed["transcription"], "a\"b\\c\td\ne\u{1}");
+    }
+
+    #[test]
+    fn detected_language_wire_format() {
+        let message = ServerMessage::Transcription {
+            seq: 0,
+            transcription: "Hello".to_string(),
+            raw_transcription: None,
+            task: None,
+            source: None,
+            detected_language: Some(DetectedLanguage {
+                language: "en".to_string(),
+                language_probability: 0.75,
+            }),
+            segments: vec![],
+            filtered: vec![],
+            duration: 1.0,
+            start: 0.0,
+            end: 1.0,
+        };
+
+        assert_eq!(
+            message.to_json(),
+            r#"{"version":1,"type":"transcription","seq":0,"transcription":"Hello","language":"en","language_probability":0.75,"segments":[],"duration":1.0,"start":0.0,"end":1.0}"#
+        );
+    }
+
+    #[test]
+    fn translation_wire_format() {
+        let message = ServerMessage::Transcription {
+            seq: 0,
+            transcription: "Hello".to_string(),
+            raw_transcription: None,
+            task: Some(WhisperTask::Translate),
+            source: Some(Box::new(SourceTranscript {
+                transcription: "こんにちは".to_string(),
+                raw_transcription: None,
+                segments: vec![],
+            })),
+            detected_language: None,
+            segments: vec![],
+            filtered: vec![],
+            duration: 1.0,
+            start: 0.0,
+            end: 1.0,
+        };
+
+        assert_eq!(
+            message.to_json(),
+            r#"{"version":1,"type":"transcription","seq":0,"transcription":"Hello","task":"translate","source":{"transcription":"こんにちは","segments":[]},"segments":[],"duration":1.0,"start":0.0,"end":1.0}"#
+        );
+    }
+
+    #[test]
+    fn raw_text_wire_format() {
+        let message = ServerMessage::Transcription {
+            seq: 0,
+            transcription: "ACMEの渡邊です".to_string(),
+            raw_transcription: Some("アクメの渡辺です".to_string()),
+            task: None,
+            source: None,
+            detected_language: None,
+            segments: vec![SegmentInfo {
+                start: 0.0,
+                end: 1.5,
+                text: "ACMEの渡邊です".to_string(),
+                raw_text: Some("アクメの渡辺です".to_string()),
+                words: None,
+                filter: None,
+            }],
+            filtered: vec![],
+            duration: 1.5,
+            start: 0.0,
+            end: 1.5,
+        };
+
+        assert_eq!(
+            message.to_json(),
+            r#"{"version":1,"type":"transcription","seq":0,"transcription":"ACMEの渡邊です","raw_transcription":"アクメの渡辺です","segments":[{"start":0.0,"end":1.5,"text":"ACMEの渡邊です","raw_text":"アクメの渡辺です"}],"duration":1.5,"start":0.0,"end":1.5}"#
+        );
+    }
+
+    #[test]
+    fn no_speech_wire_format() {
+        let message = ServerMessage::NoSpeech {
+            seq: 1,
+            filtered: vec![],
+            duration: 0.75,
+            start: 2.5,
+            end: 3.25,
+        };
+
+        assert_eq!(
+            message.to_json(),
+            r#"{"version":1,"type":"no_speech","seq":1,"duration":0.75,"start":2.5,"end":3.25}"#
+        );
+    }
+
+    #[test]
+    fn filtered_segments_wire_format() {
+        let message = ServerMessage::NoSpeech {
+            seq: 5,
+            filtered: vec![FilteredSegment {
+                start: 6.0,
+                end: 7.5,
+                text: "ご視聴ありがとうございました。".to_string(),
+                reason: FilterReason::NgWord,
+            }],
+            duration: 1.5,
+            start: 6.0,
+            end: 7.5,
+        };
+        assert_eq!(
+            message.to_json(),
+            r#"{"version":1,"type":"no_speech","seq":5,"filtered":[{"start":6.0,"end":7.5,"text":"ご視聴ありがとうございました。","reason":"ng_word"}],"duration":1.5,"start":6.0,"end":7.5}"#
+        );
+
+        let flagged = SegmentInfo {
+            start: 0.0,
+            end: 2.0,
+            text: "はい。はい。はい。はい。".to_string(),
+            raw_text: None,
+            words: None,
+            filter: Some(FilterReason::Repetition),
+        };
+        assert_eq!(
+            serde_json::to_string(&flagged).unwrap(),
+            r#"{"start":0.0,"end":2.0,"text":"はい。はい。はい。はい。","filter":"repetition"}"#
+        );
+    }
+
+    #[test]
+    fn partial_and_final_wire_format() {
+        let partial = ServerMessage::Partial {
+            seq: 2,
+            transcription: "こんにち".to_string(),
+            raw_transcription: None,
+            task: None,
+            detected_language: None,
+            duration: 1.0,
+            start: 4.0,
+            end: 5.0,
+        };
+        assert_eq!(
+            partial.to_json(),
+            r#"{"version":1,"type":"partial","seq":2,"transcription":"こんにち","duration":1.0,"start":4.0,"end":5.0}"#
+        );
+
+        let final_message = ServerMessage::Final {
+            seq: 2,
+            transcription: "こんにちは".to_string(),
+            raw_transcription: None,
+            task: None,
+            source: None,
+            detected_language: None,
+            segments: vec![SegmentInfo {
+                start: 4.0,
+                end: 5.5,
+                text: "こんにちは".to_string(),
+                raw_text: None,
+                words: None,
+                filter: None,
+            }],
+            filtered: vec![],
+            duration: 1.5,
+            start: 4.0,
+            end: 5.5,
+        };
+        assert_eq!(
+            final_message.to_json(),
+            r#"{"version":1,"type":"final","seq":2,"transcription":"こんにちは","segments":[{"start":4.0,"end":5.5,"text":"こんにちは"}],"duration":1.5,"start":4.0,"end":5.5}"#
+        );
+    }
+
+    #[test]
+    fn error_wire_format() {
+        assert_eq!(
+            ServerMessage::error("VAD initialization failed: \"model\" not found").to_json(),
+            r#"{"version":1,"type":"error","message":"VAD initialization failed: \"model\" not found"}"#
+        );
+
+        let message = ServerMessage::Error {
+            seq: Some(4),
+            message: "Transcription failed".to_string(),
+        };
+        assert_eq!(
+            message.to_json(),
+            r#"{"version":1,"type":"error","seq":4,"message":"Transcription failed"}"#
+        );
+    }
+
+    #[test]
+    fn status_wire_format() {
+        assert_eq!(
+            ServerMessage::status(Status::Ready).to_json(),
+            r#"{"version":1,"type":"status","status":"ready"}"#
+        );
+
+        let message = ServerMessage::Status {
+            status: Status::ConfigApplied,
+            config: Some(SessionSettings {
+                language: "auto".to_string(),
+                language_candidates: vec!["ja".to_string(), "en".to_string()],
+                task: WhisperTask::Transcribe,
+                include_source: false,
+                prompt: Some("Rust, whisper.cpp".to_string()),
+                context_tokens: 64,
+                vad_threshold: 0.6,
+                vad_min_speech_duration_ms: 250,
+                vad_max_speech_duration_seconds: 10.0,
+                vad_min_silence_duration_ms: 100,
+                vad_speech_pad_ms: 30,
+                block_seconds: 10,
+                min_speech_samples: 8000,
+                partial_interval_ms: 0,
+                word_timestamps: false,
+                dictionary: Some("names".to_string()),
+                include_raw: false,
+                encoding: Encoding::Mulaw,
+                sample_rate: 8000,
+                channels: 1,
+            }),
+        };
+
+        assert_eq!(
+            message.to_json(),
+            r#"{"version":1,"type":"status","status":"config_applied","config":{"language":"auto","language_candidates":["ja","en"],"task":"transcribe","include_source":false,"prompt":"Rust, whisper.cpp","context_tokens":64,"vad_threshold":0.6,"vad_min_speech_duration_ms":250,"vad_max_speech_duration_seconds":10.0,"vad_min_silence_duration_ms":100,"vad_speech_pad_ms":30,"block_seconds":10,"min_speech_samples":8000,"partial_interval_ms":0,"word_timestamps":false,"dictionary":"names","include_raw":false,"encoding":"mulaw","sample_rate":8000,"channels":1}}"#
+        );
+    }
+
+    #[test]
+    fn word_timestamps_wire_format() {
+        let segment = SegmentInfo {
+            start: 1.0,
+            end: 1.8,
+            text: "Hello world".to_string(),
+            raw_text: None,
+            words: Some(vec![
+                WordInfo {
+                    text: "Hello".to_string(),
+                    start: 1.0,
+                    end: 1.3,
+                    probability: 0.5,
+                },
+                WordInfo {
+                    text: "world".to_string(),
+                    start: 1.4,
+                    end: 1.8,
+                    probability: 0.25,
+                },
+            ]),
+            filter: None,
+        };
+
+        assert_eq!(
+            serde_json::to_string(&segment).unwrap(),
+            r#"{"start":1.0,"end":1.8,"text":"Hello world","words":[{"text":"Hello","start":1.0,"end":1.3,"probability":0.5},{"text":"world","start":1.4,"end":1.8,"probability":0.25}]}"#
+        );
+    }
+
+    #[test]
+    fn subtitle_wire_format() {
+        let message = ServerMessage::Subtitle {
+            format: SubtitleFormat::Srt,
+            content: "1\n00:00:01,000 --> 00:00:02,500\nこんにちは\n\n".to_string(),
+        };
+
+        assert_eq!(
+            message.to_json(),
+            r#"{"version":1,"type":"subtitle","format":"srt","content":"1\n00:00:01,000 --> 00:00:02,500\nこんにちは\n\n"}"#
+        );
+    }
+
+    #[test]
+    fn round_time_keeps_centiseconds() {
+        assert_eq!(round_time(12.399999999), 12.4);
+        assert_eq!(round_time(1.234), 1.23);
+        assert_eq!(round_time(3.0), 3.0);
+    }
+}
@@ -0,0 +1,110 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tokio::net::TcpListener;
+use tokio_tungstenite::accept_async;
+
+use crate::config::Config;
+use crate::transcriber::load_model;
+use crate::vad::{VadFactory, load_vad};
+use crate::worker::WorkerPool;
+use crate::{connection, http};
+
+/// Runs the WebSocket server, and the HTTP API when enabled, until Ctrl+C
+pub async fn serve(config: Config) -> Result<(), String> {
+    // Ctrl+C handler
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let shutdown_clone = shutdown.clone();
+
+    ctrlc::set_handler(move || {
+        println!("\n🛑 Shutting down gracefully...");
+        shutdown_clone.store(true, Ordering::SeqCst);
+    })
+    .map_err(|e| format!("Error setting Ctrl-C handler: {}", e))?;
+
+    let bind_addr = format!("{}:{}", config.host, config.port);
+    let listener = TcpListener::bind(&bind_addr)
+        .await
+        .map_err(|e| format!("Failed to bind {}: {}", bind_addr, e))?;
+    println!("🚀 WebSocket server running on {}", bind_addr);
+    println!("Press Ctrl+C to stop");
+
+    // Worker pool for processing transcription
+    let ctx = Arc::new(load_model(&config)?);
+    let pool = WorkerPool::whisper(ctx, &config, shutdown.clone())?;
+    let pool = Arc::new(pool);
+    // One VAD session shared by every connection and HTTP request
+    let vad = load_vad(&config)?;
+
+    // OpenAI-compatible HTTP API
+    if config.http_port != 0 {
+        let http_addr = format!("{}:{}", config.host, config.http_port);
+        let http_listener = TcpListener::bind(&http_addr)
+            .await
+            .map_err(|e| format!("Failed to bind {}: {}", http_addr, e))?;
+        println!("🌐 HTTP API running on http://{}", http_addr);
+        tokio::spawn(http::serve(
+            http_listener,
+            config.clone(),
+            pool.clone(),
+            vad.clone(),
+        ));
+    }
+
+    serve_websocket(listener, config, pool, vad, shutdown).await;
+    Ok(())
+}
+
+/// Runs a WebSocket session for every client of `listener` until `shutdown` is set or Ctrl+C
+pub async fn serve_websocket(
+    listener: TcpListener,
+    config: Config,
+    pool: Arc<WorkerPool>,
+    vad: VadFactory,
+    shutdown: Arc<AtomicBool>,
+) {
+    loop {
+        if shutdown.load(Ordering::SeqCst) {
+            println!("✅ Server stopped");
+            break;
+        }
+
+        tokio::select! {
+            result = listener.accept() => {
+                let (stream, addr) = match result {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        eprintln!("❌ Accept error: {}", e);
+                        continue;
+                    }
+                };
+
+                if shutdown.load(Ordering::SeqCst) {
+                    break;
+                }
+
+                let ws = match accept_async(stream).await {
+                    Ok(ws) => ws,
+                    Err(e) => {
+                        eprintln!("❌ WebSocket handshake error: {}", e);
+                        continue;
+                    }
+                };
+
+                println!("🔗 Client connected: {}", addr);
+
+                tokio::spawn(connection::handle_connection(
+                    ws,
+                    addr,
+                    config.clone(),
+                    pool.clone(),
+                    vad.clone(),
+                ));
+            }
+            _ = tokio::signal::ctrl_c() => {
+                println!("✅ Server stopped");
+                break;
+            }
+        }
+    }
+}
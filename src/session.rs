@@ -0,0 +1,275 @@
+use serde::Deserialize;
+
+use crate::audio::{
+    Encoding, MAX_CHANNELS, MAX_INPUT_SAMPLE_RATE, MIN_INPUT_SAMPLE_RATE, PIPELINE_SAMPLE_RATE,
+};
+use crate::config::Config;
+use crate::subtitle::{SubtitleFormat, SubtitleOptions};
+use crate::worker::{AUTO_LANGUAGE, WhisperTask, is_supported_language};
+
+// Limits for the server configuration and per-session overrides
+pub const MAX_BLOCK_SECONDS: usize = 60;
+pub const MAX_DURATION_MS: i32 = 10_000;
+pub const MAX_SPEECH_PAD_MS: i32 = 1_000;
+pub const MIN_PARTIAL_INTERVAL_MS: u64 = 200;
+pub const MAX_SUBTITLE_LINES: usize = 10;
+// Whisper uses at most half of its 448-token text context for the prompt
+pub const MAX_CONTEXT_TOKENS: usize = 224;
+
+/// Control messages a client can send as JSON text frames
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ControlMessage {
+    Config(Box<SessionConfig>),
+    /// Sends the session transcript so far as subtitles
+    Export(ExportRequest),
+    /// Ends the session once every queued utterance is answered
+    Close(CloseRequest),
+}
+
+/// Subtitle export of the session transcript.
+/// Missing fields keep the server's subtitle settings.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExportRequest {
+    pub format: SubtitleFormat,
+    pub max_line_width: Option<usize>,
+    pub max_lines: Option<usize>,
+    pub max_cue_seconds: Option<f64>,
+}
+
+impl ExportRequest {
+    /// Apply the overrides on top of `base`
+    pub fn options(&self, base: &SubtitleOptions) -> Result<SubtitleOptions, String> {
+        let mut options = *base;
+
+        if let Some(width) = self.max_line_width {
+            if width == 0 {
+                return Err("max_line_width must be positive".to_string());
+            }
+            options.max_line_width = width;
+        }
+
+        if let Some(lines) = self.max_lines {
+            if lines == 0 || lines > MAX_SUBTITLE_LINES {
+                return Err(format!(
+                    "max_lines must be between 1 and {} (got {})",
+                    MAX_SUBTITLE_LINES, lines
+                ));
+            }
+            options.max_lines = lines;
+        }
+
+        if let Some(seconds) = self.max_cue_seconds {
+            if seconds <= 0.0 {
+                return Err(format!(
+                    "max_cue_seconds must be positive (got {})",
+                    seconds
+                ));
+            }
+            options.max_cue_seconds = seconds;
+        }
+
+        Ok(options)
+    }
+}
+
+/// Close request; with a format, the full transcript is sent before closing
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CloseRequest {
+    pub format: Option<SubtitleFormat>,
+}
+
+/// Per-session overrides of the global configuration.
+/// Every field is optional; missing fields keep the server default.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SessionConfig {
+    pub language: Option<String>,
+    pub language_candidates: Option<Vec<String>>,
+    pub task: Option<String>,
+    pub include_source: Option<bool>,
+    pub prompt: Option<String>,
+    pub context_tokens: Option<usize>,
+    pub vad_threshold: Option<f32>,
+    pub vad_min_speech_duration_ms: Option<i32>,
+    pub vad_max_speech_duration_seconds: Option<f32>,
+    pub vad_min_silence_duration_ms: Option<i32>,
+    pub vad_speech_pad_ms: Option<i32>,
+    pub block_seconds: Option<usize>,
+    pub min_speech_samples: Option<usize>,
+    pub partial_interval_ms: Option<u64>,
+    pub word_timestamps: Option<bool>,
+    // Replacement dictionary name; empty disables it
+    pub dictionary: Option<String>,
+    pub include_raw: Option<bool>,
+    pub encoding: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub channels: Option<u16>,
+}
+
+impl SessionConfig {
+    /// Apply the overrides on top of `base`, checking each value against the server limits
+    pub fn apply(&self, base: &Config) -> Result<Config, String> {
+        let mut config = base.clone();
+
+        if let Some(language) = &self.language {
+            if !is_supported_language(language) {
+                return Err(format!("Unsupported language: {}", language));
+            }
+            config.whisper_language = language.clone();
+        }
+
+        if let Some(candidates) = &self.language_candidates {
+            if let Some(language) = candidates
+                .iter()
+                .find(|c| c.as_str() == AUTO_LANGUAGE || !is_supported_language(c))
+            {
+                return Err(format!("Unsupported candidate language: {}", language));
+            }
+            config.language_candidates = candidates.clone();
+        }
+
+        if let Some(task) = &self.task {
+            config.whisper_task = task.parse::<WhisperTask>()?;
+        }
+
+        if let Some(include_source) = self.include_source {
+            config.include_source = include_source;
+        }
+
+        if let Some(prompt) = &self.prompt {
+            if prompt.contains('\0') {
+                return Err("prompt must not contain NUL characters".to_string());
+            }
+            config.whisper_prompt = Some(prompt.clone()).filter(|p| !p.trim().is_empty());
+        }
+
+        if let Some(tokens) = self.context_tokens {
+            if tokens > MAX_CONTEXT_TOKENS {
+                return Err(format!(
+                    "context_tokens must be between 0 and {} (got {})",
+                    MAX_CONTEXT_TOKENS, tokens
+                ));
+            }
+            config.context_tokens = tokens;
+        }
+
+        if let Some(threshold) = self.vad_threshold {
+            if threshold <= 0.0 || threshold >= 1.0 {
+                return Err(format!(
+                    "vad_threshold must be between 0 and 1 (got {})",
+                    threshold
+                ));
+            }
+            config.vad_threshold = threshold;
+        }
+
+        if let Some(ms) = self.vad_min_speech_duration_ms {
+            config.vad_min_speech_duration_ms =
+                check_duration_ms("vad_min_speech_duration_ms", ms, MAX_DURATION_MS)?;
+        }
+
+        if let Some(seconds) = self.vad_max_speech_duration_seconds {
+            if seconds <= 0.0 {
+                return Err(format!(
+                    "vad_max_speech_duration_seconds must be positive (got {})",
+                    seconds
+                ));
+            }
+            config.vad_max_speech_duration_seconds = seconds;
+        }
+
+        if let Some(ms) = self.vad_min_silence_duration_ms {
+            config.vad_min_silence_duration_ms =
+                check_duration_ms("vad_min_silence_duration_ms", ms, MAX_DURATION_MS)?;
+        }
+
+        if let Some(ms) = self.vad_speech_pad_ms {
+            config.vad_speech_pad_ms =
+                check_duration_ms("vad_speech_pad_ms", ms, MAX_SPEECH_PAD_MS)?;
+        }
+
+        if let Some(seconds) = self.block_seconds {
+            if seconds == 0 || seconds > MAX_BLOCK_SECONDS {
+                return Err(format!(
+                    "block_seconds must be between 1 and {} (got {})",
+                    MAX_BLOCK_SECONDS, seconds
+                ));
+            }
+            config.whisper_block_seconds = seconds;
+        }
+
+        if let Some(samples) = self.min_speech_samples {
+            config.min_speech_samples = samples;
+        }
+
+        if let Some(ms) = self.partial_interval_ms {
+            if ms != 0 && !(MIN_PARTIAL_INTERVAL_MS..=MAX_DURATION_MS as u64).contains(&ms) {
+                return Err(format!(
+                    "partial_interval_ms must be 0 or between {} and {} (got {})",
+                    MIN_PARTIAL_INTERVAL_MS, MAX_DURATION_MS, ms
+                ));
+            }
+            config.partial_interval_ms = ms;
+        }
+
+        if let Some(enabled) = self.word_timestamps {
+            config.word_timestamps = enabled;
+        }
+
+        if let Some(name) = &self.dictionary {
+            config.dictionary = Some(name.clone()).filter(|name| !name.is_empty());
+        }
+
+        if let Some(include_raw) = self.include_raw {
+            config.include_raw = include_raw;
+        }
+
+        if let Some(encoding) = &self.encoding {
+            config.encoding = encoding.parse::<Encoding>()?;
+        }
+
+        if let Some(rate) = self.sample_rate {
+            if !(MIN_INPUT_SAMPLE_RATE..=MAX_INPUT_SAMPLE_RATE).contains(&rate) {
+                return Err(format!(
+                    "sample_rate must be between {} and {} (got {})",
+                    MIN_INPUT_SAMPLE_RATE, MAX_INPUT_SAMPLE_RATE, rate
+                ));
+            }
+            config.sample_rate = rate;
+        }
+
+        if let Some(channels) = self.channels {
+            if channels == 0 || channels > MAX_CHANNELS {
+                return Err(format!(
+                    "channels must be between 1 and {} (got {})",
+                    MAX_CHANNELS, channels
+                ));
+            }
+            config.channels = channels;
+        }
+
+        // Checked last so that it also covers a changed block size
+        let block_size = PIPELINE_SAMPLE_RATE as usize * config.whisper_block_seconds;
+        if config.min_speech_samples >= block_size {
+            return Err(format!(
+                "min_speech_samples must be smaller than the block size ({} >= {})",
+                config.min_speech_samples, block_size
+            ));
+        }
+
+        Ok(config)
+    }
+}
+
+fn check_duration_ms(key: &str, value: i32, max: i32) -> Result<i32, String> {
+    if !(0..=max).contains(&value) {
+        return Err(format!(
+            "{} must be between 0 and {} (got {})",
+            key, max, value
+        ));
+    }
+    Ok(value)
+}
@@ -0,0 +1,329 @@
+use serde::{Deserialize, Serialize};
+
+use crate::protocol::SegmentInfo;
+
+// Characters that must not start a line (kinsoku shori)
+const NO_LINE_START: &str = "、。，．・：；？！ー―‐…‥ゝゞヽヾ々）」』】〕〉》］｝ぁぃぅぇぉっゃゅょゎァィゥェォッャュョヮヵヶ),.!?:;%]}";
+// Characters that must not end a line
+const NO_LINE_END: &str = "（「『【〔〈《［｛([{";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubtitleFormat {
+    Srt,
+    Vtt,
+}
+
+/// Line wrapping and timing rules for subtitle cues
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SubtitleOptions {
+    // Line width in columns; CJK characters take two columns
+    pub max_line_width: usize,
+    pub max_lines: usize,
+    pub max_cue_seconds: f64,
+}
+
+/// One caption on screen
+#[derive(Debug, Clone, PartialEq)]
+struct Cue {
+    start: f64,
+    end: f64,
+    lines: Vec<String>,
+}
+
+/// Renders segments as a subtitle document of the given format
+pub fn render(
+    segments: &[SegmentInfo],
+    format: SubtitleFormat,
+    options: &SubtitleOptions,
+) -> String {
+    match format {
+        SubtitleFormat::Srt => to_srt(segments, options),
+        SubtitleFormat::Vtt => to_vtt(segments, options),
+    }
+}
+
+/// Renders segments as SubRip cues
+pub fn to_srt(segments: &[SegmentInfo], options: &SubtitleOptions) -> String {
+    let mut output = String::new();
+    for (index, cue) in build_cues(segments, options).iter().enumerate() {
+        output.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            index + 1,
+            format_timestamp(cue.start, ','),
+            format_timestamp(cue.end, ','),
+            cue.lines.join("\n")
+        ));
+    }
+    output
+}
+
+/// Renders segments as a WebVTT document
+pub fn to_vtt(segments: &[SegmentInfo], options: &SubtitleOptions) -> String {
+    let mut output = String::from("WEBVTT\n\n");
+    for cue in build_cues(segments, options) {
+        let lines: Vec<String> = cue.lines.iter().map(|line| escape_vtt(line)).collect();
+        output.push_str(&format!(
+            "{} --> {}\n{}\n\n",
+            format_timestamp(cue.start, '.'),
+            format_timestamp(cue.end, '.'),
+            lines.join("\n")
+        ));
+    }
+    output
+}
+
+/// Wraps each segment and splits it into cues of at most `max_lines` lines.
+/// The segment's time is shared out between its cues by text width.
+fn build_cues(segments: &[SegmentInfo], options: &SubtitleOptions) -> Vec<Cue> {
+    let mut cues: Vec<Cue> = Vec::new();
+
+    for segment in segments {
+        let text = segment
+            .text
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        let lines = wrap(&text, options.max_line_width.max(1));
+        if lines.is_empty() {
+            continue;
+        }
+
+        let total: usize = lines.iter().map(|line| text_width(line)).sum();
+        let span = (segment.end - segment.start).max(0.0);
+        let mut start = segment.start;
+        let mut done = 0;
+        for chunk in lines.chunks(options.max_lines.max(1)) {
+            done += chunk.iter().map(|line| text_width(line)).sum::<usize>();
+            let end = segment.start + span * done as f64 / total.max(1) as f64;
+            cues.push(Cue {
+                start,
+                end: end.min(start + options.max_cue_seconds),
+                lines: chunk.to_vec(),
+            });
+            start = end;
+        }
+    }
+
+    // A cue ends before the next one starts
+    cues.sort_by(|a, b| a.start.total_cmp(&b.start));
+    for i in 1..cues.len() {
+        let next_start = cues[i].start;
+        if cues[i - 1].end > next_start {
+            cues[i - 1].end = next_start;
+        }
+    }
+    cues
+}
+
+/// Unbreakable piece of text
+struct Token {
+    text: String,
+    space_before: bool,
+}
+
+/// Splits text where a line may break: at spaces, and around CJK characters
+/// unless that would put punctuation at the start or an opening bracket at the end of a line
+fn tokenize(text: &str) -> Vec<Token> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut space = false;
+    let mut previous: Option<char> = None;
+
+    for c in text.chars() {
+        if c.is_whitespace() {
+            space = true;
+            previous = None;
+            continue;
+        }
+
+        let can_break = match previous {
+            None => true,
+            Some(p) => {
+                (is_wide(p) || is_wide(c)) && !NO_LINE_START.contains(c) && !NO_LINE_END.contains(p)
+            }
+        };
+        match tokens.last_mut() {
+            Some(token) if !can_break => token.text.push(c),
+            _ => tokens.push(Token {
+                text: c.to_string(),
+                space_before: space,
+            }),
+        }
+
+        space = false;
+        previous = Some(c);
+    }
+    tokens
+}
+
+/// Greedy line wrapping; a token wider than a line gets a line of its own
+fn wrap(text: &str, max_width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut width = 0;
+
+    for token in tokenize(text) {
+        let token_width = text_width(&token.text);
+        let space = usize::from(token.space_before && !line.is_empty());
+        if !line.is_empty() && width + space + token_width > max_width {
+            lines.push(std::mem::take(&mut line));
+            width = 0;
+        } else if space == 1 {
+            line.push(' ');
+            width += 1;
+        }
+        line.push_str(&token.text);
+        width += token_width;
+    }
+
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Display width in columns
+fn text_width(text: &str) -> usize {
+    text.chars().map(|c| if is_wide(c) { 2 } else { 1 }).sum()
+}
+
+/// East Asian wide and full-width characters
+fn is_wide(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x1100..=0x115F
+            | 0x2E80..=0x303E
+            | 0x3041..=0x33FF
+            | 0x3400..=0x4DBF
+            | 0x4E00..=0x9FFF
+            | 0xA000..=0xA4CF
+            | 0xAC00..=0xD7A3
+            | 0xF900..=0xFAFF
+            | 0xFE30..=0xFE4F
+            | 0xFF00..=0xFF60
+            | 0xFFE0..=0xFFE6
+            | 0x20000..=0x3FFFD
+    )
+}
+
+fn escape_vtt(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Formats seconds as `HH:MM:SS<separator>mmm`
+fn format_timestamp(seconds: f64, separator: char) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        separator,
+        millis % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPTIONS: SubtitleOptions = SubtitleOptions {
+        max_line_width: 42,
+        max_lines: 2,
+        max_cue_seconds: 7.0,
+    };
+
+    fn segment(start: f64, end: f64, text: &str) -> SegmentInfo {
+        SegmentInfo {
+            start,
+            end,
+            text: text.to_string(),
+            raw_text: None,
+            words: None,
+            filter: None,
+        }
+    }
+
+    fn segments() -> Vec<SegmentInfo> {
+        vec![
+            segment(0.0, 2.5, "こんにちは"),
+            segment(3661.25, 3662.0, "世界"),
+        ]
+    }
+
+    #[test]
+    fn srt_output() {
+        assert_eq!(
+            to_srt(&segments(), &OPTIONS),
+            "1\n00:00:00,000 --> 00:00:02,500\nこんにちは\n\n2\n01:01:01,250 --> 01:01:02,000\n世界\n\n"
+        );
+    }
+
+    #[test]
+    fn vtt_output() {
+        assert_eq!(
+            to_vtt(&segments(), &OPTIONS),
+            "WEBVTT\n\n00:00:00.000 --> 00:00:02.500\nこんにちは\n\n01:01:01.250 --> 01:01:02.000\n世界\n\n"
+        );
+    }
+
+    #[test]
+    fn latin_text_wraps_at_spaces() {
+        assert_eq!(
+            wrap("the quick brown fox jumps over the lazy dog", 16),
+            vec!["the quick brown", "fox jumps over", "the lazy dog"]
+        );
+    }
+
+    #[test]
+    fn cjk_text_wraps_by_width_without_leading_punctuation() {
+        // Ten columns fit five CJK characters; "。" stays on the line it closes
+        assert_eq!(
+            wrap("今日はいい天気です。明日も晴れ", 10),
+            vec!["今日はいい", "天気です。", "明日も晴れ"]
+        );
+        // Brackets stay with the text they enclose
+        assert_eq!(
+            wrap("これは「例」です", 8),
+            vec!["これは", "「例」で", "す"]
+        );
+        // Latin words inside CJK text are kept whole
+        assert_eq!(wrap("これはRustの例", 8), vec!["これは", "Rustの例"]);
+    }
+
+    #[test]
+    fn long_segment_is_split_into_timed_cues() {
+        let options = SubtitleOptions {
+            max_line_width: 10,
+            max_lines: 1,
+            max_cue_seconds: 7.0,
+        };
+        let cues = build_cues(&[segment(10.0, 14.0, "aaaa bbbb cccc dddd")], &options);
+
+        assert_eq!(cues.len(), 2);
+        assert_eq!(cues[0].lines, vec!["aaaa bbbb"]);
+        assert_eq!((cues[0].start, cues[0].end), (10.0, 12.0));
+        assert_eq!(cues[1].lines, vec!["cccc dddd"]);
+        assert_eq!((cues[1].start, cues[1].end), (12.0, 14.0));
+    }
+
+    #[test]
+    fn cues_are_capped_and_do_not_overlap() {
+        let cues = build_cues(
+            &[segment(0.0, 20.0, "長い発話"), segment(5.0, 6.0, "重なり")],
+            &OPTIONS,
+        );
+        assert_eq!((cues[0].start, cues[0].end), (0.0, 5.0));
+
+        let cues = build_cues(&[segment(0.0, 20.0, "長い発話")], &OPTIONS);
+        assert_eq!((cues[0].start, cues[0].end), (0.0, 7.0));
+    }
+
+    #[test]
+    fn vtt_escapes_markup() {
+        let output = to_vtt(&[segment(0.0, 1.0, "a <b> & c --> d")], &OPTIONS);
+        assert!(output.contains("a &lt;b&gt; &amp; c --&gt; d\n"));
+    }
+}
@@ -0,0 +1,72 @@
+pub mod scripted;
+pub mod whisper;
+
+pub use scripted::{ScriptedCall, ScriptedTranscriber};
+pub use whisper::{WhisperTranscriber, load_model};
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::filter::SegmentStats;
+use crate::protocol::{DetectedLanguage, WordInfo};
+
+/// Cancellation flag shared by a session and the tasks it queued
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// Options of one transcription run
+#[derive(Debug, Clone, Copy)]
+pub struct TranscribeOptions<'a> {
+    // Language code of the audio
+    pub language: &'a str,
+    // Static prompt, e.g. domain vocabulary
+    pub prompt: Option<&'a str>,
+    // Recent session text; its last `context_tokens` tokens follow the prompt
+    pub context: &'a str,
+    pub context_tokens: usize,
+    // Produces an English translation instead of text in the spoken language
+    pub translate: bool,
+    pub temperature: Option<f32>,
+    // Fills in the words of each segment
+    pub word_timestamps: bool,
+    // Set once the result is no longer wanted; the run may stop early
+    pub cancel: &'a CancelToken,
+}
+
+/// Recognized text with times in seconds from the start of the audio
+#[derive(Debug, Clone)]
+pub struct Segment {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+    pub stats: SegmentStats,
+    // Empty unless word timestamps were requested
+    pub words: Vec<WordInfo>,
+}
+
+/// Speech recognition backend. Each worker owns one and runs one request at a time.
+pub trait Transcriber: Send {
+    /// Recognizes 16 kHz mono audio
+    fn transcribe(
+        &mut self,
+        audio: &[f32],
+        options: &TranscribeOptions,
+    ) -> Result<Vec<Segment>, String>;
+
+    /// Most likely language of the audio among `candidates`, or among all when empty
+    fn detect_language(
+        &mut self,
+        audio: &[f32],
+        candidates: &[String],
+    ) -> Result<DetectedLanguage, String>;
+}
@@ -0,0 +1,253 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{Segment, TranscribeOptions, Transcriber};
+use crate::audio::PIPELINE_SAMPLE_RATE;
+use crate::filter::SegmentStats;
+use crate::protocol::{DetectedLanguage, WordInfo};
+
+// How often a delayed response checks for cancellation
+const CANCEL_POLL: Duration = Duration::from_millis(5);
+
+/// Request seen by a [`ScriptedTranscriber`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptedCall {
+    pub samples: usize,
+    pub language: String,
+    pub prompt: Option<String>,
+    pub context: String,
+    pub translate: bool,
+}
+
+#[derive(Debug, Default)]
+struct Script {
+    responses: VecDeque<Vec<String>>,
+    calls: Vec<ScriptedCall>,
+}
+
+/// Transcriber answering with canned text, for running the server without a model.
+///
+/// Each call takes the next queued response, or finds no speech once the script
+/// runs out. The texts of a response become segments splitting the audio evenly.
+/// Clones share one script, so a pool of several workers answers in call order.
+#[derive(Debug, Clone)]
+pub struct ScriptedTranscriber {
+    script: Arc<Mutex<Script>>,
+    // Time each call takes, as if the model were running
+    delay: Duration,
+    // Reported by language detection
+    language: String,
+}
+
+impl Default for ScriptedTranscriber {
+    fn default() -> Self {
+        Self {
+            script: Arc::default(),
+            delay: Duration::ZERO,
+            language: "en".to_string(),
+        }
+    }
+}
+
+impl ScriptedTranscriber {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn with_language(mut self, language: &str) -> Self {
+        self.language = language.to_string();
+        self
+    }
+
+    /// Queues the segment texts of the next call
+    pub fn respond(&self, texts: &[&str]) {
+        let texts = texts.iter().map(|text| text.to_string()).collect();
+        self.script.lock().unwrap().responses.push_back(texts);
+    }
+
+    /// Requests answered so far, in order
+    pub fn calls(&self) -> Vec<ScriptedCall> {
+        self.script.lock().unwrap().calls.clone()
+    }
+
+    /// Waits out the delay; false if cancelled in the meantime
+    fn wait(&self, options: &TranscribeOptions) -> bool {
+        let deadline = Instant::now() + self.delay;
+        loop {
+            if options.cancel.is_cancelled() {
+                return false;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return true;
+            }
+            thread::sleep(CANCEL_POLL.min(deadline - now));
+        }
+    }
+}
+
+impl Transcriber for ScriptedTranscriber {
+    fn transcribe(
+        &mut self,
+        audio: &[f32],
+        options: &TranscribeOptions,
+    ) -> Result<Vec<Segment>, String> {
+        let texts = {
+            let mut script = self.script.lock().unwrap();
+            script.calls.push(ScriptedCall {
+                samples: audio.len(),
+                language: options.language.to_string(),
+                prompt: options.prompt.map(str::to_string),
+                context: options.context.to_string(),
+                translate: options.translate,
+            });
+            script.responses.pop_front().unwrap_or_default()
+        };
+
+        if !self.wait(options) {
+            return Err("Transcription cancelled".to_string());
+        }
+
+        let duration = audio.len() as f64 / PIPELINE_SAMPLE_RATE as f64;
+        let span = duration / texts.len().max(1) as f64;
+        Ok(texts
+            .into_iter()
+            .enumerate()
+            .map(|(i, text)| {
+                let start = i as f64 * span;
+                let words = if options.word_timestamps {
+                    spread_words(&text, start, start + span)
+                } else {
+                    Vec::new()
+                };
+                Segment {
+                    start,
+                    end: start + span,
+                    text,
+                    stats: SegmentStats {
+                        no_speech_probability: 0.0,
+                        avg_logprob: 0.0,
+                    },
+                    words,
+                }
+            })
+            .collect())
+    }
+
+    fn detect_language(
+        &mut self,
+        _audio: &[f32],
+        candidates: &[String],
+    ) -> Result<DetectedLanguage, String> {
+        // The configured language, unless the candidates rule it out
+        let language = match candidates.first() {
+            Some(first) if !candidates.contains(&self.language) => first.clone(),
+            _ => self.language.clone(),
+        };
+        Ok(DetectedLanguage {
+            language,
+            language_probability: 1.0,
+        })
+    }
+}
+
+/// Whitespace-separated words of `text`, each getting an equal share of the time
+fn spread_words(text: &str, start: f64, end: f64) -> Vec<WordInfo> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let span = (end - start) / words.len().max(1) as f64;
+    words
+        .into_iter()
+        .enumerate()
+        .map(|(i, word)| WordInfo {
+            text: word.to_string(),
+            start: start + i as f64 * span,
+            end: start + (i + 1) as f64 * span,
+            probability: 1.0,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcriber::CancelToken;
+
+    fn options(cancel: &CancelToken) -> TranscribeOptions<'_> {
+        TranscribeOptions {
+            language: "en",
+            prompt: Some("names"),
+            context: "",
+            context_tokens: 0,
+            translate: false,
+            temperature: None,
+            word_timestamps: true,
+            cancel,
+        }
+    }
+
+    #[test]
+    fn responses_split_the_audio_in_call_order() {
+        let mut transcriber = ScriptedTranscriber::new();
+        transcriber.respond(&["hello there", "world"]);
+        let cancel = CancelToken::default();
+        let audio = vec![0.0; PIPELINE_SAMPLE_RATE as usize * 2];
+
+        let segments = transcriber.transcribe(&audio, &options(&cancel)).unwrap();
+        assert_eq!(segments.len(), 2);
+        assert_eq!((segments[1].start, segments[1].end), (1.0, 2.0));
+        assert_eq!(segments[0].words[1].text, "there");
+        assert_eq!(
+            (segments[0].words[1].start, segments[0].words[1].end),
+            (0.5, 1.0)
+        );
+
+        // The script ran out
+        assert!(
+            transcriber
+                .transcribe(&audio, &options(&cancel))
+                .unwrap()
+                .is_empty()
+        );
+        let calls = transcriber.calls();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].samples, audio.len());
+        assert_eq!(calls[0].prompt.as_deref(), Some("names"));
+    }
+
+    #[test]
+    fn cancellation_cuts_the_delay_short() {
+        let mut transcriber = ScriptedTranscriber::new().with_delay(Duration::from_secs(60));
+        transcriber.respond(&["never"]);
+        let cancel = CancelToken::default();
+        cancel.cancel();
+
+        assert!(
+            transcriber
+                .transcribe(&[0.0; 160], &options(&cancel))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn detection_respects_the_candidates() {
+        let mut transcriber = ScriptedTranscriber::new().with_language("ja");
+        let detect = |transcriber: &mut ScriptedTranscriber, candidates: &[&str]| {
+            let candidates: Vec<String> = candidates.iter().map(|c| c.to_string()).collect();
+            transcriber
+                .detect_language(&[], &candidates)
+                .unwrap()
+                .language
+        };
+
+        assert_eq!(detect(&mut transcriber, &[]), "ja");
+        assert_eq!(detect(&mut transcriber, &["en", "ja"]), "ja");
+        assert_eq!(detect(&mut transcriber, &["de", "fr"]), "de");
+    }
+}
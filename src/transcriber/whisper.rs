@@ -0,0 +1,304 @@
+use std::ffi::c_void;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use whisper_rs::{
+    FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters, WhisperSegment,
+    WhisperState,
+};
+
+use super::{Segment, TranscribeOptions, Transcriber};
+use crate::config::Config;
+use crate::filter::SegmentStats;
+use crate::protocol::{DetectedLanguage, WordInfo};
+use crate::words::{TimedToken, merge_tokens};
+
+/// Loads the Whisper model of `config`
+pub fn load_model(config: &Config) -> Result<WhisperContext, String> {
+    println!("📦 Loading Whisper model: {}", config.whisper_model_path);
+    let ctx = WhisperContext::new_with_params(
+        &config.whisper_model_path,
+        WhisperContextParameters::default(),
+    )
+    .map_err(|e| {
+        format!(
+            "Failed to load Whisper model {}: {}",
+            config.whisper_model_path, e
+        )
+    })?;
+    println!("✅ Whisper model loaded");
+    Ok(ctx)
+}
+
+/// Abort callback for whisper.cpp; `user_data` points at the task's cancellation flag
+unsafe extern "C" fn abort_if_cancelled(user_data: *mut c_void) -> bool {
+    // SAFETY: the flag is kept alive by the task for the whole `full` call
+    let cancelled = unsafe { &*(user_data as *const AtomicBool) };
+    cancelled.load(Ordering::SeqCst)
+}
+
+/// whisper.cpp with its own reusable state on a shared model
+pub struct WhisperTranscriber {
+    ctx: Arc<WhisperContext>,
+    state: WhisperState,
+    threads: usize,
+    // Token ids from end-of-text on are special tokens (timestamps, language tags)
+    token_eot: i32,
+    max_prompt_tokens: usize,
+}
+
+impl WhisperTranscriber {
+    pub fn new(ctx: Arc<WhisperContext>, threads: usize) -> Result<Self, String> {
+        let state = ctx.create_state().map_err(|e| e.to_string())?;
+        let token_eot = ctx.token_eot();
+        // whisper.cpp keeps at most half of the text context for the prompt
+        let max_prompt_tokens = (ctx.n_text_ctx() / 2).max(0) as usize;
+        Ok(Self {
+            ctx,
+            state,
+            threads,
+            token_eot,
+            max_prompt_tokens,
+        })
+    }
+
+    /// Static prompt followed by as much recent session text as the budget allows
+    fn prompt_tokens(&self, options: &TranscribeOptions) -> Vec<i32> {
+        let prompt = options
+            .prompt
+            .map(|prompt| self.tokenize(prompt))
+            .unwrap_or_default();
+        let context = if options.context_tokens == 0 {
+            Vec::new()
+        } else if prompt.is_empty() {
+            self.tokenize(options.context)
+        } else {
+            self.tokenize(&format!(" {}", options.context))
+        };
+        build_prompt(
+            &prompt,
+            &context,
+            options.context_tokens,
+            self.max_prompt_tokens,
+        )
+    }
+
+    fn tokenize(&self, text: &str) -> Vec<i32> {
+        if text.trim().is_empty() {
+            return Vec::new();
+        }
+        // Every token covers at least one byte, so the result always fits
+        match self.ctx.tokenize(text, text.len() + 1) {
+            Ok(tokens) => tokens,
+            Err(e) => {
+                eprintln!("⚠️  Could not tokenize prompt: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// No-speech probability and mean token log probability of a segment
+    fn stats(&self, segment: &WhisperSegment) -> SegmentStats {
+        let logprobs: Vec<f32> = (0..segment.n_tokens())
+            .filter_map(|i| segment.get_token(i))
+            .filter(|token| token.token_id() < self.token_eot)
+            .map(|token| token.token_data().plog)
+            .collect();
+        let avg_logprob = if logprobs.is_empty() {
+            0.0
+        } else {
+            logprobs.iter().sum::<f32>() / logprobs.len() as f32
+        };
+
+        SegmentStats {
+            no_speech_probability: segment.no_speech_probability(),
+            avg_logprob,
+        }
+    }
+
+    /// Words of a segment from its token timestamps
+    fn words(&self, segment: &WhisperSegment, language: &str) -> Vec<WordInfo> {
+        let tokens: Vec<TimedToken> = (0..segment.n_tokens())
+            .filter_map(|i| segment.get_token(i))
+            .filter(|token| token.token_id() < self.token_eot)
+            .filter_map(|token| {
+                let data = token.token_data();
+                Some(TimedToken {
+                    bytes: token.to_bytes().ok()?.to_vec(),
+                    start: data.t0 as f64 / 100.0,
+                    end: data.t1 as f64 / 100.0,
+                    probability: data.p,
+                })
+            })
+            .collect();
+        merge_tokens(&tokens, language)
+    }
+}
+
+impl Transcriber for WhisperTranscriber {
+    fn transcribe(
+        &mut self,
+        audio: &[f32],
+        options: &TranscribeOptions,
+    ) -> Result<Vec<Segment>, String> {
+        let prompt = self.prompt_tokens(options);
+
+        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        params.set_language(Some(options.language));
+        params.set_translate(options.translate);
+        params.set_print_progress(false);
+        params.set_print_special(false);
+        params.set_print_realtime(false);
+        params.set_n_threads(self.threads as i32);
+        params.set_token_timestamps(options.word_timestamps);
+
+        if !prompt.is_empty() {
+            params.set_tokens(&prompt);
+        }
+        // The state is shared by every session on this worker; only the prompt carries context
+        params.set_no_context(true);
+        if let Some(temperature) = options.temperature {
+            params.set_temperature(temperature);
+        }
+
+        // Stop inference early once the result is no longer wanted.
+        // The raw callback is used because set_abort_callback_safe in whisper-rs 0.15
+        // hands its trampoline a boxed trait object instead of the closure.
+        let cancelled: *const AtomicBool = &*options.cancel.0;
+        unsafe {
+            params.set_abort_callback(Some(abort_if_cancelled));
+            params.set_abort_callback_user_data(cancelled as *mut c_void);
+        }
+
+        self.state
+            .full(params, audio)
+            .map_err(|e| format!("Transcription failed: {}", e))?;
+
+        // Translations are always English
+        let language = if options.translate {
+            "en"
+        } else {
+            options.language
+        };
+        Ok(self
+            .state
+            .as_iter()
+            .map(|segment| Segment {
+                // Centiseconds / 100 = seconds
+                start: segment.start_timestamp() as f64 / 100.0,
+                end: segment.end_timestamp() as f64 / 100.0,
+                text: segment.to_string(),
+                stats: self.stats(&segment),
+                words: if options.word_timestamps {
+                    self.words(&segment, language)
+                } else {
+                    Vec::new()
+                },
+            })
+            .collect())
+    }
+
+    fn detect_language(
+        &mut self,
+        audio: &[f32],
+        candidates: &[String],
+    ) -> Result<DetectedLanguage, String> {
+        self.state
+            .pcm_to_mel(audio, self.threads)
+            .map_err(|e| e.to_string())?;
+        let (_, probabilities) = self
+            .state
+            .lang_detect(0, self.threads)
+            .map_err(|e| e.to_string())?;
+
+        let candidates: Vec<i32> = candidates
+            .iter()
+            .filter_map(|language| whisper_rs::get_lang_id(language))
+            .collect();
+        let (id, probability) = best_language(&probabilities, &candidates)
+            .ok_or_else(|| "no candidate language".to_string())?;
+        let language =
+            whisper_rs::get_lang_str(id).ok_or_else(|| format!("unknown language id {}", id))?;
+
+        Ok(DetectedLanguage {
+            language: language.to_string(),
+            language_probability: probability,
+        })
+    }
+}
+
+/// Most likely language id and its probability.
+/// With candidates, the probability is relative to the candidates only.
+fn best_language(probabilities: &[f32], candidates: &[i32]) -> Option<(i32, f32)> {
+    let ids: Vec<i32> = if candidates.is_empty() {
+        (0..probabilities.len() as i32).collect()
+    } else {
+        candidates.to_vec()
+    };
+
+    let probability = |id: i32| probabilities.get(id as usize).copied().unwrap_or(0.0);
+    let total: f32 = ids.iter().map(|&id| probability(id)).sum();
+    let best = ids
+        .into_iter()
+        .max_by(|&a, &b| probability(a).total_cmp(&probability(b)))?;
+
+    let share = if total > 0.0 {
+        probability(best) / total
+    } else {
+        0.0
+    };
+    Some((best, share))
+}
+
+/// Joins the static prompt and the end of the session context.
+/// The context gets at most `context_budget` tokens and whatever room the prompt leaves.
+fn build_prompt(
+    prompt: &[i32],
+    context: &[i32],
+    context_budget: usize,
+    max_tokens: usize,
+) -> Vec<i32> {
+    let prompt = &prompt[..prompt.len().min(max_tokens)];
+    let budget = context_budget.min(max_tokens - prompt.len());
+    let context = &context[context.len().saturating_sub(budget)..];
+    [prompt, context].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn best_language_without_candidates() {
+        assert_eq!(best_language(&[0.1, 0.7, 0.2], &[]), Some((1, 0.7)));
+    }
+
+    #[test]
+    fn best_language_among_candidates() {
+        // Only 0 and 2 are allowed; 2 has 0.2 of their combined 0.25
+        let (id, probability) = best_language(&[0.05, 0.75, 0.2], &[0, 2]).unwrap();
+        assert_eq!(id, 2);
+        assert!((probability - 0.8).abs() < 1e-6);
+    }
+
+    #[test]
+    fn prompt_keeps_the_most_recent_context() {
+        assert_eq!(
+            build_prompt(&[1, 2], &[10, 11, 12, 13], 3, 224),
+            vec![1, 2, 11, 12, 13]
+        );
+        assert_eq!(build_prompt(&[], &[10, 11, 12], 0, 224), Vec::<i32>::new());
+    }
+
+    #[test]
+    fn prompt_fits_whisper_limit() {
+        assert_eq!(
+            build_prompt(&[1, 2, 3], &[10, 11, 12], 3, 4),
+            vec![1, 2, 3, 12]
+        );
+        assert_eq!(
+            build_prompt(&[1, 2, 3, 4, 5], &[10], 3, 4),
+            vec![1, 2, 3, 4]
+        );
+    }
+}
@@ -0,0 +1,373 @@
+use super::VoiceActivityDetector;
+use super::speech_segment::{SpeechSegment, VadEvent};
+
+// 32 ms at 16 kHz, the same window as Silero
+const FRAME_SAMPLES: usize = 512;
+// A frame this far above the noise floor scores 1.0
+const SCORE_RANGE_DB: f32 = 20.0;
+// Speech continues while the score stays above the threshold minus this
+const THRESHOLD_GAP: f32 = 0.15;
+// Lowest noise floor; anything quieter counts as silence
+const MIN_FLOOR_DB: f32 = -60.0;
+// Share of the distance to a louder frame the noise floor moves per frame,
+// slower during speech so that long utterances do not raise it
+const FLOOR_RISE: f32 = 0.01;
+const FLOOR_RISE_IN_SPEECH: f32 = 0.002;
+// Frames with more zero crossings sound like noise or fricatives;
+// they may continue speech but never start it
+const MAX_ONSET_ZCR: f32 = 0.35;
+// Consecutive voiced frames needed to start speech, so clicks are ignored
+const ONSET_FRAMES: usize = 3;
+
+/// Speech detector using short-term energy and zero-crossing rate; needs no model.
+///
+/// Each frame is scored by its level above an adaptive noise floor, 0.5 being
+/// 10 dB above it, so the threshold means about the same as with Silero. Speech
+/// starts after a few loud, voiced frames and ends once the score stayed low for
+/// the minimum silence (hangover), so short pauses do not split an utterance.
+pub struct EnergyVadDetector {
+    threshold: f32,
+    sampling_rate: i32,
+    min_speech_samples: usize,
+    min_silence_samples: usize,
+    max_speech_samples: usize,
+    speech_pad_samples: usize,
+    noise_floor_db: f32,
+
+    // Streaming state (absolute sample offsets)
+    pending: Vec<f32>,
+    position: usize,
+    fed_samples: usize,
+    // Consecutive voiced frames while not in speech, and where they began
+    onset_frames: usize,
+    onset_start: usize,
+    triggered: bool,
+    start_emitted: bool,
+    speech_start: usize,
+    // End of the last frame that counted as speech
+    speech_end: usize,
+    last_end: usize,
+}
+
+impl EnergyVadDetector {
+    pub fn new(
+        threshold: f32,
+        sampling_rate: i32,
+        min_speech_duration_ms: i32,
+        max_speech_duration_seconds: f32,
+        min_silence_duration_ms: i32,
+        speech_pad_ms: i32,
+    ) -> Self {
+        let samples = |ms: f32| (sampling_rate as f32 * ms / 1000.0) as usize;
+        let speech_pad_samples = samples(speech_pad_ms as f32);
+        // Leave room for the padding, as Silero does
+        let max_speech_samples =
+            (sampling_rate as f32 * max_speech_duration_seconds).min(usize::MAX as f32) as usize;
+        let max_speech_samples = max_speech_samples
+            .saturating_sub(FRAME_SAMPLES + 2 * speech_pad_samples)
+            .max(FRAME_SAMPLES);
+
+        Self {
+            threshold,
+            sampling_rate,
+            min_speech_samples: samples(min_speech_duration_ms as f32),
+            min_silence_samples: samples(min_silence_duration_ms as f32),
+            max_speech_samples,
+            speech_pad_samples,
+            noise_floor_db: MIN_FLOOR_DB,
+            pending: Vec::new(),
+            position: 0,
+            fed_samples: 0,
+            onset_frames: 0,
+            onset_start: 0,
+            triggered: false,
+            start_emitted: false,
+            speech_start: 0,
+            speech_end: 0,
+            last_end: 0,
+        }
+    }
+
+    fn step(&mut self, frame: &[f32], events: &mut Vec<VadEvent>) {
+        let frame_start = self.position;
+        self.position += frame.len();
+        let frame_end = self.position;
+
+        let level = level_db(frame);
+        let score = ((level - self.noise_floor_db) / SCORE_RANGE_DB).clamp(0.0, 1.0);
+        self.track_noise_floor(level);
+
+        if !self.triggered {
+            if score >= self.threshold && zero_crossing_rate(frame) <= MAX_ONSET_ZCR {
+                if self.onset_frames == 0 {
+                    self.onset_start = frame_start;
+                }
+                self.onset_frames += 1;
+                if self.onset_frames >= ONSET_FRAMES {
+                    self.triggered = true;
+                    self.start_emitted = false;
+                    self.speech_start = self.onset_start;
+                    self.speech_end = frame_end;
+                    self.onset_frames = 0;
+                }
+            } else {
+                self.onset_frames = 0;
+            }
+        } else if score >= self.threshold - THRESHOLD_GAP {
+            self.speech_end = frame_end;
+        }
+
+        if !self.triggered {
+            return;
+        }
+
+        if !self.start_emitted && self.speech_end - self.speech_start >= self.min_speech_samples {
+            self.emit_start(events);
+        }
+
+        if frame_end - self.speech_end >= self.min_silence_samples {
+            // The hangover ran out
+            if self.speech_end - self.speech_start >= self.min_speech_samples {
+                self.end_segment(self.speech_end, frame_end, events);
+            } else {
+                self.triggered = false;
+            }
+        } else if frame_end - self.speech_start >= self.max_speech_samples {
+            // Cut long speech and carry on with a new segment right after it
+            let still_speaking = self.speech_end == frame_end;
+            self.end_segment(frame_end, frame_end, events);
+            if still_speaking {
+                self.triggered = true;
+                self.speech_start = frame_end;
+                self.speech_end = frame_end;
+            }
+        }
+    }
+
+    /// Follows quieter frames at once and louder ones slowly
+    fn track_noise_floor(&mut self, level: f32) {
+        if level < self.noise_floor_db {
+            self.noise_floor_db = level;
+        } else {
+            let rise = if self.triggered {
+                FLOOR_RISE_IN_SPEECH
+            } else {
+                FLOOR_RISE
+            };
+            self.noise_floor_db += (level - self.noise_floor_db) * rise;
+        }
+        self.noise_floor_db = self.noise_floor_db.max(MIN_FLOOR_DB);
+    }
+
+    fn padded_start(&self) -> usize {
+        self.speech_start
+            .saturating_sub(self.speech_pad_samples)
+            .max(self.last_end)
+    }
+
+    fn emit_start(&mut self, events: &mut Vec<VadEvent>) {
+        events.push(VadEvent::SpeechStart {
+            offset: self.padded_start(),
+        });
+        self.start_emitted = true;
+    }
+
+    fn end_segment(&mut self, end: usize, limit: usize, events: &mut Vec<VadEvent>) {
+        if !self.start_emitted {
+            self.emit_start(events);
+        }
+
+        let start = self.padded_start();
+        let end = (end + self.speech_pad_samples)
+            .min(limit)
+            .min(self.fed_samples);
+        events.push(VadEvent::SpeechEnd(SpeechSegment::from_offsets(
+            start,
+            end,
+            self.sampling_rate,
+        )));
+
+        self.last_end = end;
+        self.triggered = false;
+        self.start_emitted = false;
+    }
+}
+
+impl VoiceActivityDetector for EnergyVadDetector {
+    fn process_chunk(
+        &mut self,
+        chunk: &[f32],
+    ) -> Result<Vec<VadEvent>, Box<dyn std::error::Error>> {
+        self.pending.extend_from_slice(chunk);
+        self.fed_samples += chunk.len();
+
+        let mut events = Vec::new();
+        let frames = self.pending.len() / FRAME_SAMPLES * FRAME_SAMPLES;
+        let samples: Vec<f32> = self.pending.drain(..frames).collect();
+        for frame in samples.chunks_exact(FRAME_SAMPLES) {
+            self.step(frame, &mut events);
+        }
+        Ok(events)
+    }
+
+    fn finish(&mut self) -> Result<Vec<VadEvent>, Box<dyn std::error::Error>> {
+        let mut events = Vec::new();
+
+        let tail = std::mem::take(&mut self.pending);
+        if !tail.is_empty() {
+            self.step(&tail, &mut events);
+        }
+
+        if self.triggered && self.speech_end - self.speech_start >= self.min_speech_samples {
+            let end = self.fed_samples;
+            self.end_segment(self.speech_end, end, &mut events);
+        }
+
+        self.triggered = false;
+        self.start_emitted = false;
+        self.onset_frames = 0;
+        Ok(events)
+    }
+
+    fn retain_offset(&self) -> usize {
+        let start = if self.triggered {
+            self.speech_start
+        } else if self.onset_frames > 0 {
+            self.onset_start
+        } else {
+            self.position
+        };
+        start
+            .saturating_sub(self.speech_pad_samples)
+            .max(self.last_end)
+    }
+}
+
+/// RMS level in dB relative to full scale
+fn level_db(frame: &[f32]) -> f32 {
+    let power = frame.iter().map(|s| s * s).sum::<f32>() / frame.len().max(1) as f32;
+    10.0 * power.max(1e-12).log10()
+}
+
+/// Share of neighbouring samples with opposite signs
+fn zero_crossing_rate(frame: &[f32]) -> f32 {
+    let crossings = frame
+        .windows(2)
+        .filter(|pair| (pair[0] >= 0.0) != (pair[1] >= 0.0))
+        .count();
+    crossings as f32 / frame.len().saturating_sub(1).max(1) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: usize = 16000;
+
+    fn detector() -> EnergyVadDetector {
+        EnergyVadDetector::new(0.5, RATE as i32, 250, f32::INFINITY, 300, 30)
+    }
+
+    fn tone(seconds: f32) -> Vec<f32> {
+        (0..(seconds * RATE as f32) as usize)
+            .map(|i| 0.3 * (i as f32 * 220.0 * std::f32::consts::TAU / RATE as f32).sin())
+            .collect()
+    }
+
+    fn silence(seconds: f32) -> Vec<f32> {
+        vec![0.0; (seconds * RATE as f32) as usize]
+    }
+
+    /// Deterministic white noise
+    fn hiss(seconds: f32, amplitude: f32) -> Vec<f32> {
+        let mut seed = 0x2545_f491u32;
+        (0..(seconds * RATE as f32) as usize)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                amplitude * (seed as f32 / u32::MAX as f32 * 2.0 - 1.0)
+            })
+            .collect()
+    }
+
+    fn segments(vad: &mut EnergyVadDetector, audio: &[f32], chunk: usize) -> Vec<(usize, usize)> {
+        let mut events = Vec::new();
+        for piece in audio.chunks(chunk) {
+            events.extend(vad.process_chunk(piece).unwrap());
+        }
+        events.extend(vad.finish().unwrap());
+        events
+            .into_iter()
+            .filter_map(|event| match event {
+                VadEvent::SpeechEnd(segment) => Some((segment.start_offset, segment.end_offset)),
+                VadEvent::SpeechStart { .. } => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn finds_tone_bursts_between_silences() {
+        let audio = [
+            silence(0.5),
+            tone(1.0),
+            silence(1.0),
+            tone(0.5),
+            silence(0.5),
+        ]
+        .concat();
+        let found = segments(&mut detector(), &audio, 1000);
+
+        assert_eq!(found.len(), 2, "{:?}", found);
+        // Within a frame plus the padding of the true bounds
+        let near = |actual: usize, expected: f32| {
+            (actual as f32 - expected * RATE as f32).abs() <= (FRAME_SAMPLES + 480) as f32
+        };
+        assert!(
+            near(found[0].0, 0.5) && near(found[0].1, 1.5),
+            "{:?}",
+            found
+        );
+        assert!(
+            near(found[1].0, 2.5) && near(found[1].1, 3.0),
+            "{:?}",
+            found
+        );
+    }
+
+    #[test]
+    fn short_pauses_stay_in_the_utterance() {
+        let audio = [tone(0.6), silence(0.15), tone(0.6), silence(1.0)].concat();
+        assert_eq!(segments(&mut detector(), &audio, 512).len(), 1);
+    }
+
+    #[test]
+    fn clicks_and_hiss_are_not_speech() {
+        let mut audio = silence(1.0);
+        audio[8000] = 0.9;
+        audio.extend(hiss(1.0, 0.3));
+        audio.extend(silence(0.5));
+        assert!(segments(&mut detector(), &audio, 4096).is_empty());
+    }
+
+    #[test]
+    fn chunk_size_does_not_matter() {
+        let audio = [silence(0.3), tone(0.8), silence(0.6), tone(0.4)].concat();
+        let expected = segments(&mut detector(), &audio, audio.len());
+        assert_eq!(expected.len(), 2);
+        for chunk in [160, 512, 3000] {
+            assert_eq!(segments(&mut detector(), &audio, chunk), expected);
+        }
+    }
+
+    #[test]
+    fn long_speech_is_cut() {
+        let mut vad = EnergyVadDetector::new(0.5, RATE as i32, 250, 2.0, 300, 30);
+        let found = segments(&mut vad, &[tone(5.0), silence(0.5)].concat(), 1000);
+
+        assert_eq!(found.len(), 3, "{:?}", found);
+        assert!(found.iter().all(|(start, end)| end - start <= 2 * RATE));
+        // The pieces follow each other without gaps
+        assert_eq!(found[0].1, found[1].0);
+    }
+}
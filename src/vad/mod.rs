@@ -3,8 +3,154 @@
 // Modified for Rust implementation
 // Licensed under the MIT License
 
+pub mod energy_vad;
+pub mod silero_model;
 pub mod silero_vad;
 pub mod speech_segment;
 
+pub use energy_vad::EnergyVadDetector;
+pub use silero_model::SileroModel;
 pub use silero_vad::SileroVadDetector;
-pub use speech_segment::SpeechSegment;
+pub use speech_segment::{SpeechSegment, VadEvent};
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::audio::PIPELINE_SAMPLE_RATE;
+use crate::config::Config;
+
+/// Streaming speech detector over 16 kHz pipeline audio.
+/// Offsets in the events are absolute sample positions from the start of the stream.
+pub trait VoiceActivityDetector: Send + Sync {
+    /// Feeds a chunk of any size, returning the speech starts and ends it completes
+    fn process_chunk(&mut self, chunk: &[f32])
+    -> Result<Vec<VadEvent>, Box<dyn std::error::Error>>;
+
+    /// Ends the current stream: processes the buffered tail and closes an open speech segment
+    fn finish(&mut self) -> Result<Vec<VadEvent>, Box<dyn std::error::Error>>;
+
+    /// Absolute offset of the earliest sample that can still be part of a future segment
+    fn retain_offset(&self) -> usize;
+}
+
+impl VoiceActivityDetector for SileroVadDetector {
+    fn process_chunk(
+        &mut self,
+        chunk: &[f32],
+    ) -> Result<Vec<VadEvent>, Box<dyn std::error::Error>> {
+        SileroVadDetector::process_chunk(self, chunk)
+    }
+
+    fn finish(&mut self) -> Result<Vec<VadEvent>, Box<dyn std::error::Error>> {
+        SileroVadDetector::finish(self)
+    }
+
+    fn retain_offset(&self) -> usize {
+        SileroVadDetector::retain_offset(self)
+    }
+}
+
+/// Speech detector implementation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VadBackend {
+    /// Silero VAD on ONNX Runtime
+    #[default]
+    Silero,
+    /// Short-term energy and zero-crossing rate; needs no model
+    Energy,
+}
+
+impl FromStr for VadBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "silero" => Ok(VadBackend::Silero),
+            "energy" => Ok(VadBackend::Energy),
+            _ => Err(format!(
+                "Unsupported VAD backend: {} (expected silero or energy)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for VadBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VadBackend::Silero => write!(f, "silero"),
+            VadBackend::Energy => write!(f, "energy"),
+        }
+    }
+}
+
+/// Loads the VAD model of the configured backend, once for the whole server
+pub fn load_vad(config: &Config) -> Result<VadFactory, String> {
+    if config.vad_backend != VadBackend::Silero {
+        return Ok(VadFactory::default());
+    }
+
+    println!("📦 Loading VAD model: {}", config.vad_model_path);
+    let factory = VadFactory::new(config)
+        .map_err(|e| format!("Failed to load VAD model {}: {}", config.vad_model_path, e))?;
+    println!("✅ VAD model loaded");
+    Ok(factory)
+}
+
+/// Creates detectors, loading the model of the configured backend only once.
+///
+/// Every Silero detector created by one factory shares its ONNX session; clones
+/// share it too, so one factory loaded at startup serves all connections.
+#[derive(Clone, Default)]
+pub struct VadFactory {
+    // Loaded for the Silero backend only
+    silero: Option<Arc<SileroModel>>,
+}
+
+impl VadFactory {
+    pub fn new(config: &Config) -> Result<Self, Box<dyn std::error::Error>> {
+        let silero = match config.vad_backend {
+            VadBackend::Silero => Some(Arc::new(SileroModel::load(&config.vad_model_path)?)),
+            VadBackend::Energy => None,
+        };
+        Ok(Self { silero })
+    }
+
+    /// Creates a detector for 16 kHz pipeline audio with the VAD settings of `config`
+    pub fn create(
+        &self,
+        config: &Config,
+    ) -> Result<Box<dyn VoiceActivityDetector>, Box<dyn std::error::Error>> {
+        // An utterance is never longer than one block, so long speech is still split
+        let max_speech_seconds = config
+            .vad_max_speech_duration_seconds
+            .min(config.whisper_block_seconds as f32);
+
+        Ok(match config.vad_backend {
+            VadBackend::Silero => {
+                let model = self
+                    .silero
+                    .clone()
+                    .ok_or("Silero VAD model is not loaded")?;
+                Box::new(SileroVadDetector::with_model(
+                    model,
+                    config.vad_threshold,
+                    PIPELINE_SAMPLE_RATE as i32,
+                    config.vad_min_speech_duration_ms,
+                    max_speech_seconds,
+                    config.vad_min_silence_duration_ms,
+                    config.vad_speech_pad_ms,
+                )?)
+            }
+            VadBackend::Energy => Box::new(EnergyVadDetector::new(
+                config.vad_threshold,
+                PIPELINE_SAMPLE_RATE as i32,
+                config.vad_min_speech_duration_ms,
+                max_speech_seconds,
+                config.vad_min_silence_duration_ms,
+                config.vad_speech_pad_ms,
+            )),
+        })
+    }
+}
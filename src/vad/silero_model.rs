@@ -0,0 +1,210 @@
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+
+use ndarray::{Array1, Array2, Array3, Axis, concatenate, s};
+use ort::session::Session;
+use ort::value::Tensor;
+
+// Most requests run at once; the rest wait for the next run
+const MAX_BATCH: usize = 64;
+
+type RunResult = Result<(Array2<f32>, Array3<f32>), String>;
+
+/// One detector's input waiting for the inference thread
+struct Request {
+    input: Array2<f32>,
+    state: Array3<f32>,
+    sr: i64,
+    reply: mpsc::SyncSender<RunResult>,
+}
+
+/// Silero ONNX session shared by every detector.
+///
+/// The session lives on its own inference thread. Detectors keep their own recurrent
+/// state and context and send them in with each run; requests that queue up while a
+/// run is in progress go into the next run together, one row of the batch each.
+pub struct SileroModel {
+    requests: mpsc::Sender<Request>,
+}
+
+impl SileroModel {
+    /// Loads the model and starts its inference thread, which ends once the model is dropped
+    pub fn load(model_path: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let session = Session::builder()?
+            .with_intra_threads(1)?
+            .commit_from_file(model_path)?;
+
+        let (requests, queue) = mpsc::channel();
+        thread::Builder::new()
+            .name("silero-vad".to_string())
+            .spawn(move || run_queue(session, queue))?;
+        Ok(Self { requests })
+    }
+
+    /// Runs the model on `input` (batch x samples, context included) from `state`
+    /// (2 x batch x 128), returning the speech probability of each row and the next state.
+    /// Blocks until the inference thread answers, so keep it off the async runtime.
+    pub fn run(
+        &self,
+        input: Array2<f32>,
+        state: Array3<f32>,
+        sr: i64,
+    ) -> Result<(Array2<f32>, Array3<f32>), Box<dyn std::error::Error>> {
+        let (reply, result) = mpsc::sync_channel(1);
+        self.requests
+            .send(Request {
+                input,
+                state,
+                sr,
+                reply,
+            })
+            .map_err(|_| "VAD inference thread stopped")?;
+
+        let output = result
+            .recv()
+            .map_err(|_| "VAD inference thread stopped")??;
+        Ok(output)
+    }
+}
+
+/// Inference thread: runs everything queued since the last run as one batch
+fn run_queue(mut session: Session, queue: mpsc::Receiver<Request>) {
+    while let Ok(first) = queue.recv() {
+        let mut requests = vec![first];
+        requests.extend(queue.try_iter().take(MAX_BATCH - 1));
+        run_batches(&mut session, requests);
+    }
+}
+
+/// Rows of one batch must share the sample rate and the window size
+fn run_batches(session: &mut Session, mut requests: Vec<Request>) {
+    while let Some(first) = requests.first() {
+        let (sr, width) = (first.sr, first.input.ncols());
+        let (batch, rest): (Vec<Request>, Vec<Request>) = requests
+            .into_iter()
+            .partition(|request| request.sr == sr && request.input.ncols() == width);
+        requests = rest;
+        run_batch(session, batch);
+    }
+}
+
+fn run_batch(session: &mut Session, requests: Vec<Request>) {
+    match infer(session, &requests) {
+        Ok((output, state)) => {
+            let rows: Vec<usize> = requests.iter().map(|r| r.input.nrows()).collect();
+            let parts = split_rows(&output, &state, &rows);
+            for (request, part) in requests.into_iter().zip(parts) {
+                let _ = request.reply.send(Ok(part));
+            }
+        }
+        Err(e) => {
+            let message = e.to_string();
+            for request in requests {
+                let _ = request.reply.send(Err(message.clone()));
+            }
+        }
+    }
+}
+
+/// Stacks the requests along the batch axis and runs the session once
+fn infer(
+    session: &mut Session,
+    requests: &[Request],
+) -> Result<(Array2<f32>, Array3<f32>), Box<dyn std::error::Error>> {
+    let inputs: Vec<_> = requests.iter().map(|r| r.input.view()).collect();
+    let states: Vec<_> = requests.iter().map(|r| r.state.view()).collect();
+    let input = concatenate(Axis(0), &inputs)?;
+    let state = concatenate(Axis(1), &states)?;
+    let sr = Array1::from_vec(vec![requests[0].sr]);
+
+    let outputs = session.run(ort::inputs![
+        "input" => Tensor::from_array(input)?,
+        "sr" => Tensor::from_array(sr)?,
+        "state" => Tensor::from_array(state)?,
+    ])?;
+
+    let (shape, data) = outputs["output"].try_extract_tensor::<f32>()?;
+    let output = Array2::from_shape_vec(dims::<2>(shape)?, data.to_vec())?;
+    let (shape, data) = outputs["stateN"].try_extract_tensor::<f32>()?;
+    let state = Array3::from_shape_vec(dims::<3>(shape)?, data.to_vec())?;
+    Ok((output, state))
+}
+
+/// Dimensions of an output tensor with `N` axes
+fn dims<const N: usize>(shape: &[i64]) -> Result<[usize; N], String> {
+    let dims: Vec<usize> = shape.iter().map(|&dim| dim as usize).collect();
+    dims.try_into()
+        .map_err(|_| format!("Unexpected VAD output shape {:?}", shape))
+}
+
+/// Splits a batched output and state back into consecutive groups of `rows`
+fn split_rows(
+    output: &Array2<f32>,
+    state: &Array3<f32>,
+    rows: &[usize],
+) -> Vec<(Array2<f32>, Array3<f32>)> {
+    let mut start = 0;
+    rows.iter()
+        .map(|&count| {
+            let end = start + count;
+            let part = (
+                output.slice(s![start..end, ..]).to_owned(),
+                state.slice(s![.., start..end, ..]).to_owned(),
+            );
+            start = end;
+            part
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn batched_results_go_back_to_their_requests() {
+        let output = Array2::from_shape_fn((3, 1), |(row, _)| row as f32);
+        let state = Array3::from_shape_fn((2, 3, 128), |(layer, row, _)| (layer * 10 + row) as f32);
+
+        let parts = split_rows(&output, &state, &[1, 2]);
+        assert_eq!(parts[0].0.as_slice().unwrap(), &[0.0]);
+        assert_eq!(parts[1].0.as_slice().unwrap(), &[1.0, 2.0]);
+        assert_eq!(parts[1].1.dim(), (2, 2, 128));
+        assert_eq!(parts[1].1[[1, 0, 0]], 11.0);
+    }
+
+    #[test]
+    fn output_shape_must_match() {
+        assert_eq!(dims::<2>(&[4, 1]).unwrap(), [4, 1]);
+        assert!(dims::<3>(&[4, 1]).is_err());
+    }
+
+    /// Runs the real model when VAD_MODEL_PATH points at it
+    #[test]
+    fn concurrent_runs_share_the_model() {
+        let Ok(path) = std::env::var("VAD_MODEL_PATH") else {
+            return;
+        };
+        let model = Arc::new(SileroModel::load(path).unwrap());
+
+        // 64 samples of context and a 512 sample window of silence per run
+        let runs: Vec<_> = (0..8)
+            .map(|_| {
+                let model = model.clone();
+                thread::spawn(move || {
+                    model
+                        .run(Array2::zeros((1, 576)), Array3::zeros((2, 1, 128)), 16000)
+                        .map_err(|e| e.to_string())
+                })
+            })
+            .collect();
+        for run in runs {
+            let (output, state) = run.join().unwrap().unwrap();
+            assert_eq!(output.dim(), (1, 1));
+            assert!(output[[0, 0]] < 0.5);
+            assert_eq!(state.dim(), (2, 1, 128));
+        }
+    }
+}
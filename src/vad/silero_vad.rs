@@ -3,18 +3,41 @@
 // Modified for Rust implementation
 // Licensed under the MIT License
 
-use ndarray::{Array1, Array2, Array3};
-use ort::{Session, Value};
+use ndarray::{Array2, Array3};
 use std::path::Path;
+use std::sync::Arc;
 
-use super::speech_segment::SpeechSegment;
+use super::silero_model::SileroModel;
+use super::speech_segment::{SpeechSegment, VadEvent};
 
 const THRESHOLD_GAP: f32 = 0.15;
 const SAMPLING_RATE_8K: i32 = 8000;
 const SAMPLING_RATE_16K: i32 = 16000;
 
+// Batch of windows, one row per stream
+type Windows = Vec<Vec<f32>>;
+
 pub struct SileroVadDetector {
-    session: Session,
+    sampling_rate: i32,
+
+    // Model state variables
+    model: ModelState,
+
+    // Streaming state (absolute sample offsets)
+    stream: StreamSegmenter,
+}
+
+/// Recurrent state and context of one stream on the shared model
+struct ModelState {
+    model: Arc<SileroModel>,
+    state: Array3<f32>,
+    context: Array2<f32>,
+    last_sr: i32,
+    last_batch_size: usize,
+}
+
+/// Cuts a stream into speech segments from the speech probability of each window
+struct StreamSegmenter {
     threshold: f32,
     neg_threshold: f32,
     sampling_rate: i32,
@@ -24,13 +47,23 @@ pub struct SileroVadDetector {
     max_speech_samples: f32,
     min_silence_samples: f32,
     min_silence_samples_at_max_speech: f32,
-    audio_length_samples: usize,
+    stream: StreamState,
+}
 
-    // State variables
-    state: Array3<f32>,
-    context: Array2<f32>,
-    last_sr: i32,
-    last_batch_size: usize,
+#[derive(Default)]
+struct StreamState {
+    pending: Vec<f32>,
+    position: usize,
+    fed_samples: usize,
+    triggered: bool,
+    start_emitted: bool,
+    speech_start: usize,
+    // End of the last window above the threshold, within the samples fed
+    speech_end: usize,
+    temp_end: usize,
+    prev_end: usize,
+    next_start: usize,
+    last_end: usize,
 }
 
 impl SileroVadDetector {
@@ -42,25 +75,108 @@ impl SileroVadDetector {
         max_speech_duration_seconds: f32,
         min_silence_duration_ms: i32,
         speech_pad_ms: i32,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let model = Arc::new(SileroModel::load(model_path)?);
+        Self::with_model(
+            model,
+            threshold,
+            sampling_rate,
+            min_speech_duration_ms,
+            max_speech_duration_seconds,
+            min_silence_duration_ms,
+            speech_pad_ms,
+        )
+    }
+
+    /// Detector on a model shared with other detectors; its stream state stays its own
+    pub fn with_model(
+        model: Arc<SileroModel>,
+        threshold: f32,
+        sampling_rate: i32,
+        min_speech_duration_ms: i32,
+        max_speech_duration_seconds: f32,
+        min_silence_duration_ms: i32,
+        speech_pad_ms: i32,
     ) -> Result<Self, Box<dyn std::error::Error>> {
         if sampling_rate != SAMPLING_RATE_8K && sampling_rate != SAMPLING_RATE_16K {
             return Err("Sampling rate not supported, only available for [8000, 16000]".into());
         }
 
-        let session = Session::builder()?
-            .with_intra_threads(1)?
-            .commit_from_file(model_path)?;
+        let stream = StreamSegmenter::new(
+            threshold,
+            sampling_rate,
+            min_speech_duration_ms,
+            max_speech_duration_seconds,
+            min_silence_duration_ms,
+            speech_pad_ms,
+        );
+        let model = ModelState {
+            model,
+            state: Array3::zeros((2, 1, 128)),
+            context: Array2::zeros((0, 0)),
+            last_sr: 0,
+            last_batch_size: 0,
+        };
+
+        Ok(Self {
+            sampling_rate,
+            model,
+            stream,
+        })
+    }
 
-        let window_size_sample = if sampling_rate == SAMPLING_RATE_16K { 512 } else { 256 };
+    pub fn reset_states(&mut self) {
+        self.model.reset_states();
+    }
+
+    /// Feeds a chunk of any size to the streaming detector.
+    /// The model state is kept between calls, so speech crossing chunk boundaries is not split.
+    pub fn process_chunk(
+        &mut self,
+        chunk: &[f32],
+    ) -> Result<Vec<VadEvent>, Box<dyn std::error::Error>> {
+        let (model, sr) = (&mut self.model, self.sampling_rate);
+        self.stream
+            .process_chunk(chunk, |window| model.probability(window, sr))
+    }
+
+    /// Ends the current stream: processes the buffered tail and closes an open speech segment.
+    /// Offsets keep counting from the same origin afterwards.
+    pub fn finish(&mut self) -> Result<Vec<VadEvent>, Box<dyn std::error::Error>> {
+        let (model, sr) = (&mut self.model, self.sampling_rate);
+        self.stream.finish(|window| model.probability(window, sr))
+    }
+
+    /// Absolute offset of the earliest sample that can still be part of a future segment.
+    /// Audio before this offset may be discarded by the caller.
+    pub fn retain_offset(&self) -> usize {
+        self.stream.retain_offset()
+    }
+}
+
+impl StreamSegmenter {
+    fn new(
+        threshold: f32,
+        sampling_rate: i32,
+        min_speech_duration_ms: i32,
+        max_speech_duration_seconds: f32,
+        min_silence_duration_ms: i32,
+        speech_pad_ms: i32,
+    ) -> Self {
+        let window_size_sample = if sampling_rate == SAMPLING_RATE_16K {
+            512
+        } else {
+            256
+        };
         let min_speech_samples = sampling_rate as f32 * min_speech_duration_ms as f32 / 1000.0;
         let speech_pad_samples = sampling_rate as f32 * speech_pad_ms as f32 / 1000.0;
         let max_speech_samples = sampling_rate as f32 * max_speech_duration_seconds
-            - window_size_sample as f32 - 2.0 * speech_pad_samples;
+            - window_size_sample as f32
+            - 2.0 * speech_pad_samples;
         let min_silence_samples = sampling_rate as f32 * min_silence_duration_ms as f32 / 1000.0;
         let min_silence_samples_at_max_speech = sampling_rate as f32 * 98.0 / 1000.0;
 
-        let mut detector = Self {
-            session,
+        Self {
             threshold,
             neg_threshold: threshold - THRESHOLD_GAP,
             sampling_rate,
@@ -70,42 +186,212 @@ impl SileroVadDetector {
             max_speech_samples,
             min_silence_samples,
             min_silence_samples_at_max_speech,
-            audio_length_samples: 0,
-            state: Array3::zeros((2, 1, 128)),
-            context: Array2::zeros((0, 0)),
-            last_sr: 0,
-            last_batch_size: 0,
-        };
-
-        detector.reset_states();
-        Ok(detector)
+            stream: StreamState::default(),
+        }
     }
 
-    pub fn reset_states(&mut self) {
-        self.state = Array3::zeros((2, 1, 128));
-        self.context = Array2::zeros((0, 0));
-        self.last_sr = 0;
-        self.last_batch_size = 0;
+    /// Runs `probability` on every whole window of the buffered audio
+    fn process_chunk(
+        &mut self,
+        chunk: &[f32],
+        mut probability: impl FnMut(&[f32]) -> Result<f32, Box<dyn std::error::Error>>,
+    ) -> Result<Vec<VadEvent>, Box<dyn std::error::Error>> {
+        self.stream.pending.extend_from_slice(chunk);
+        self.stream.fed_samples += chunk.len();
+
+        let mut events = Vec::new();
+        let window_count = self.stream.pending.len() / self.window_size_sample;
+        if window_count == 0 {
+            return Ok(events);
+        }
+
+        let windows: Vec<f32> = self
+            .stream
+            .pending
+            .drain(..window_count * self.window_size_sample)
+            .collect();
+
+        for window in windows.chunks_exact(self.window_size_sample) {
+            let speech_prob = probability(window)?;
+            self.step_stream(speech_prob, &mut events);
+        }
+
+        Ok(events)
     }
 
-    pub fn get_speech_segments(&mut self, input: &[f32]) -> Result<Vec<SpeechSegment>, Box<dyn std::error::Error>> {
-        self.reset_states();
-        let mut speech_prob_list = Vec::new();
-        self.audio_length_samples = input.len();
+    fn finish(
+        &mut self,
+        mut probability: impl FnMut(&[f32]) -> Result<f32, Box<dyn std::error::Error>>,
+    ) -> Result<Vec<VadEvent>, Box<dyn std::error::Error>> {
+        let mut events = Vec::new();
 
-        let mut i = 0;
-        while i < input.len() {
-            let end = (i + self.window_size_sample).min(input.len());
+        if !self.stream.pending.is_empty() {
             let mut buffer = vec![0.0f32; self.window_size_sample];
-            buffer[..end - i].copy_from_slice(&input[i..end]);
+            buffer[..self.stream.pending.len()].copy_from_slice(&self.stream.pending);
+            self.stream.pending.clear();
+
+            let speech_prob = probability(&buffer)?;
+            self.step_stream(speech_prob, &mut events);
+        }
+
+        let end = self.stream.fed_samples;
+        if self.stream.triggered
+            && (end - self.stream.speech_start) as f32 > self.min_speech_samples
+        {
+            self.end_stream_segment(end, end, &mut events);
+        }
+
+        self.stream.position = end;
+        self.stream.triggered = false;
+        self.stream.start_emitted = false;
+        self.stream.temp_end = 0;
+        self.stream.prev_end = 0;
+        self.stream.next_start = 0;
+
+        Ok(events)
+    }
 
-            let speech_prob = self.call(&[buffer], self.sampling_rate)?[0];
-            speech_prob_list.push(speech_prob);
+    fn retain_offset(&self) -> usize {
+        let pad = self.speech_pad_samples as usize;
+        let offset = if self.stream.triggered {
+            self.stream.speech_start.saturating_sub(pad)
+        } else {
+            self.stream.position.saturating_sub(pad)
+        };
+        offset.max(self.stream.last_end)
+    }
+
+    fn step_stream(&mut self, speech_prob: f32, events: &mut Vec<VadEvent>) {
+        let window_start = self.stream.position;
+        self.stream.position += self.window_size_sample;
+        let window_end = self.stream.position;
+
+        if speech_prob >= self.threshold && self.stream.temp_end != 0 {
+            self.stream.temp_end = 0;
+            if self.stream.next_start < self.stream.prev_end {
+                self.stream.next_start = window_start;
+            }
+        }
+
+        if speech_prob >= self.threshold && !self.stream.triggered {
+            self.stream.triggered = true;
+            self.stream.start_emitted = false;
+            self.stream.speech_start = window_start;
+            self.stream.prev_end = 0;
+            self.stream.next_start = 0;
+        }
+
+        if speech_prob >= self.threshold {
+            // The zero-padded tail window only counts up to the samples fed
+            self.stream.speech_end = window_end.min(self.stream.fed_samples);
+        }
+
+        // Only speech that cannot be dropped as too short any more is announced, so every
+        // start gets its end: a later silence ends at or after `speech_end`
+        if self.stream.triggered
+            && !self.stream.start_emitted
+            && (self.stream.speech_end - self.stream.speech_start) as f32 > self.min_speech_samples
+        {
+            self.emit_stream_start(events);
+        }
+
+        if !self.stream.triggered {
+            return;
+        }
+
+        if (window_start - self.stream.speech_start) as f32 > self.max_speech_samples {
+            if self.stream.prev_end != 0 {
+                let prev_end = self.stream.prev_end;
+                let next_start = self.stream.next_start;
+                self.end_stream_segment(prev_end, window_end, events);
+
+                // Continue with the speech that followed the silence, if any
+                if next_start >= prev_end {
+                    self.stream.triggered = true;
+                    self.stream.speech_start = next_start;
+                }
+            } else {
+                self.end_stream_segment(window_start, window_end, events);
+            }
+            return;
+        }
 
-            i += self.window_size_sample;
+        if speech_prob < self.neg_threshold {
+            if self.stream.temp_end == 0 {
+                self.stream.temp_end = window_start;
+            }
+
+            let silence = (window_start - self.stream.temp_end) as f32;
+            if silence > self.min_silence_samples_at_max_speech {
+                self.stream.prev_end = self.stream.temp_end;
+            }
+
+            if silence >= self.min_silence_samples {
+                let end = self.stream.temp_end;
+                if self.stream.start_emitted
+                    || (end - self.stream.speech_start) as f32 > self.min_speech_samples
+                {
+                    self.end_stream_segment(end, window_end, events);
+                } else {
+                    self.stream.triggered = false;
+                    self.stream.temp_end = 0;
+                    self.stream.prev_end = 0;
+                    self.stream.next_start = 0;
+                }
+            }
+        }
+    }
+
+    fn emit_stream_start(&mut self, events: &mut Vec<VadEvent>) {
+        let offset = self
+            .stream
+            .speech_start
+            .saturating_sub(self.speech_pad_samples as usize)
+            .max(self.stream.last_end);
+        events.push(VadEvent::SpeechStart { offset });
+        self.stream.start_emitted = true;
+    }
+
+    fn end_stream_segment(&mut self, end: usize, limit: usize, events: &mut Vec<VadEvent>) {
+        if !self.stream.start_emitted {
+            self.emit_stream_start(events);
         }
 
-        Ok(self.calculate_prob(&speech_prob_list))
+        let start = self
+            .stream
+            .speech_start
+            .saturating_sub(self.speech_pad_samples as usize)
+            .max(self.stream.last_end);
+        let end = (end + self.speech_pad_samples as usize)
+            .min(limit)
+            .min(self.stream.fed_samples);
+
+        events.push(VadEvent::SpeechEnd(SpeechSegment::from_offsets(
+            start,
+            end,
+            self.sampling_rate,
+        )));
+
+        self.stream.last_end = end;
+        self.stream.triggered = false;
+        self.stream.start_emitted = false;
+        self.stream.temp_end = 0;
+        self.stream.prev_end = 0;
+        self.stream.next_start = 0;
+    }
+}
+
+impl ModelState {
+    fn reset_states(&mut self) {
+        self.state = Array3::zeros((2, 1, 128));
+        self.context = Array2::zeros((0, 0));
+        self.last_sr = 0;
+        self.last_batch_size = 0;
+    }
+
+    /// Speech probability of one window
+    fn probability(&mut self, window: &[f32], sr: i32) -> Result<f32, Box<dyn std::error::Error>> {
+        Ok(self.call(&[window.to_vec()], sr)?[0])
     }
 
     fn call(&mut self, x: &[Vec<f32>], sr: i32) -> Result<Vec<f32>, Box<dyn std::error::Error>> {
@@ -143,19 +429,10 @@ impl SileroVadDetector {
         let input_shape = [x.len(), x[0].len()];
         let input_tensor = Array2::from_shape_vec(input_shape, input_array)?;
 
-        let sr_tensor = Array1::from_vec(vec![sr as i64]);
-        let state_tensor = self.state.clone();
-
-        let inputs = vec![
-            ("input", Value::from_array(input_tensor)?),
-            ("sr", Value::from_array(sr_tensor)?),
-            ("state", Value::from_array(state_tensor)?),
-        ];
-
-        let outputs = self.session.run(inputs)?;
-
-        let output: Array2<f32> = outputs["output"].try_extract_tensor()?.to_owned().into_dimensionality()?;
-        let new_state: Array3<f32> = outputs["stateN"].try_extract_tensor()?.to_owned().into_dimensionality()?;
+        // Runs batched with the windows of other detectors sharing the model
+        let (output, new_state) = self
+            .model
+            .run(input_tensor, self.state.clone(), sr as i64)?;
 
         self.context = self.get_last_columns(&x, context_size);
         self.last_sr = sr;
@@ -165,7 +442,11 @@ impl SileroVadDetector {
         Ok(output.into_iter().collect())
     }
 
-    fn validate_input(&self, mut x: &[Vec<f32>], mut sr: i32) -> Result<(Vec<Vec<f32>>, i32), Box<dyn std::error::Error>> {
+    fn validate_input(
+        &self,
+        x: &[Vec<f32>],
+        mut sr: i32,
+    ) -> Result<(Windows, i32), Box<dyn std::error::Error>> {
         let mut x_vec = x.to_vec();
 
         if sr != 16000 && sr % 16000 == 0 {
@@ -185,7 +466,8 @@ impl SileroVadDetector {
             return Err(format!(
                 "Only supports sample rates {} or {} (or multiples of 16000)",
                 SAMPLING_RATE_8K, SAMPLING_RATE_16K
-            ).into());
+            )
+            .into());
         }
 
         if !x_vec.is_empty() && (sr as f32 / x_vec[0].len() as f32) > 31.25 {
@@ -230,146 +512,129 @@ impl SileroVadDetector {
 
         result
     }
+}
 
-    fn calculate_prob(&self, speech_prob_list: &[f32]) -> Vec<SpeechSegment> {
-        let mut result = Vec::new();
-        let mut triggered = false;
-        let mut temp_end = 0usize;
-        let mut prev_end = 0usize;
-        let mut next_start = 0usize;
-        let mut segment_start = 0usize;
-        let mut segment_end = 0usize;
-        let mut is_detecting = false;
-
-        for (i, &speech_prob) in speech_prob_list.iter().enumerate() {
-            if speech_prob >= self.threshold && temp_end != 0 {
-                temp_end = 0;
-                if next_start < prev_end {
-                    next_start = self.window_size_sample * i;
-                }
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            if speech_prob >= self.threshold && !triggered {
-                triggered = true;
-                is_detecting = true;
-                segment_start = self.window_size_sample * i;
-                continue;
-            }
+    const RATE: usize = 16000;
 
-            if triggered && (self.window_size_sample * i - segment_start) as f32 > self.max_speech_samples {
-                if prev_end != 0 {
-                    segment_end = prev_end;
-                    result.push(SpeechSegment::from_offsets(segment_start, segment_end, self.sampling_rate));
-
-                    if next_start < prev_end {
-                        triggered = false;
-                        is_detecting = false;
-                    } else {
-                        segment_start = next_start;
-                        is_detecting = true;
-                    }
-
-                    prev_end = 0;
-                    next_start = 0;
-                    temp_end = 0;
-                } else {
-                    segment_end = self.window_size_sample * i;
-                    result.push(SpeechSegment::from_offsets(segment_start, segment_end, self.sampling_rate));
-
-                    prev_end = 0;
-                    next_start = 0;
-                    temp_end = 0;
-                    triggered = false;
-                    is_detecting = false;
-                    continue;
-                }
-            }
+    fn segmenter(max_speech_seconds: f32) -> StreamSegmenter {
+        StreamSegmenter::new(0.5, RATE as i32, 250, max_speech_seconds, 100, 30)
+    }
 
-            if speech_prob < self.neg_threshold && triggered {
-                if temp_end == 0 {
-                    temp_end = self.window_size_sample * i;
-                }
+    /// Stands in for the model: loud windows are speech
+    fn loudness(window: &[f32]) -> Result<f32, Box<dyn std::error::Error>> {
+        let level = window.iter().map(|s| s.abs()).sum::<f32>() / window.len() as f32;
+        Ok(if level > 0.1 { 0.9 } else { 0.05 })
+    }
 
-                if (self.window_size_sample * i - temp_end) as f32 > self.min_silence_samples_at_max_speech {
-                    prev_end = temp_end;
-                }
+    fn speech(seconds: f32) -> Vec<f32> {
+        vec![0.5; (seconds * RATE as f32) as usize]
+    }
 
-                if (self.window_size_sample * i - temp_end) as f32 < self.min_silence_samples {
-                    continue;
-                } else {
-                    segment_end = temp_end;
-                    if (segment_end - segment_start) as f32 > self.min_speech_samples {
-                        result.push(SpeechSegment::from_offsets(segment_start, segment_end, self.sampling_rate));
-                    }
-
-                    prev_end = 0;
-                    next_start = 0;
-                    temp_end = 0;
-                    triggered = false;
-                    is_detecting = false;
-                    continue;
-                }
-            }
-        }
+    fn silence(seconds: f32) -> Vec<f32> {
+        vec![0.0; (seconds * RATE as f32) as usize]
+    }
 
-        if is_detecting && (self.audio_length_samples - segment_start) as f32 > self.min_speech_samples {
-            segment_end = self.audio_length_samples;
-            result.push(SpeechSegment::from_offsets(segment_start, segment_end, self.sampling_rate));
+    /// All events of a stream fed in chunks, then finished
+    fn events(stream: &mut StreamSegmenter, audio: &[f32], chunk: usize) -> Vec<VadEvent> {
+        let mut events = Vec::new();
+        for piece in audio.chunks(chunk) {
+            events.extend(stream.process_chunk(piece, loudness).unwrap());
         }
+        events.extend(stream.finish(loudness).unwrap());
+        events
+    }
 
-        // Apply speech padding
-        for i in 0..result.len() {
-            if i == 0 {
-                result[i].start_offset = result[i].start_offset.saturating_sub(self.speech_pad_samples as usize);
-            }
-
-            if i != result.len() - 1 {
-                let silence_duration = result[i + 1].start_offset - result[i].end_offset;
-                if (silence_duration as f32) < 2.0 * self.speech_pad_samples {
-                    result[i].end_offset += silence_duration / 2;
-                    result[i + 1].start_offset = result[i + 1].start_offset.saturating_sub(silence_duration / 2);
-                } else {
-                    result[i].end_offset = (result[i].end_offset as f32 + self.speech_pad_samples).min(self.audio_length_samples as f32) as usize;
-                    result[i + 1].start_offset = result[i + 1].start_offset.saturating_sub(self.speech_pad_samples as usize);
+    /// Segments of the events, checking that every start is followed by its end
+    fn segments(events: &[VadEvent]) -> Vec<(usize, usize)> {
+        let mut open = None;
+        let mut found = Vec::new();
+        for event in events {
+            match event {
+                VadEvent::SpeechStart { offset } => {
+                    assert!(open.is_none(), "start inside a segment: {:?}", events);
+                    open = Some(*offset);
+                }
+                VadEvent::SpeechEnd(segment) => {
+                    assert_eq!(open.take(), Some(segment.start_offset), "{:?}", events);
+                    found.push((segment.start_offset, segment.end_offset));
                 }
-            } else {
-                result[i].end_offset = (result[i].end_offset as f32 + self.speech_pad_samples).min(self.audio_length_samples as f32) as usize;
             }
-
-            // Recalculate seconds
-            result[i].start_second = (result[i].start_offset as f32 / self.sampling_rate as f32 * 1000.0).floor() / 1000.0;
-            result[i].end_second = (result[i].end_offset as f32 / self.sampling_rate as f32 * 1000.0).floor() / 1000.0;
         }
-
-        self.merge_segments(result)
+        assert!(open.is_none(), "start without an end: {:?}", events);
+        found
     }
 
-    fn merge_segments(&self, mut segments: Vec<SpeechSegment>) -> Vec<SpeechSegment> {
-        if segments.is_empty() {
-            return segments;
+    #[test]
+    fn chunk_size_does_not_matter() {
+        let audio = [silence(0.3), speech(0.8), silence(0.6), speech(0.4)].concat();
+        let expected = segments(&events(&mut segmenter(f32::INFINITY), &audio, audio.len()));
+        assert_eq!(expected.len(), 2);
+        for chunk in [160, 512, 3000] {
+            assert_eq!(
+                segments(&events(&mut segmenter(f32::INFINITY), &audio, chunk)),
+                expected
+            );
         }
+    }
 
-        if segments.len() == 1 {
-            return segments;
-        }
+    #[test]
+    fn speech_across_blocks_is_one_segment() {
+        let audio = [silence(0.7), speech(0.9), silence(0.5)].concat();
+        let mut stream = segmenter(f32::INFINITY);
+
+        // One second blocks; the speech starts in the first and ends in the second
+        let first = stream.process_chunk(&audio[..RATE], loudness).unwrap();
+        assert!(
+            matches!(first[..], [VadEvent::SpeechStart { .. }]),
+            "{:?}",
+            first
+        );
+        // The start of the speech is still kept
+        assert!(stream.retain_offset() <= (0.7 * RATE as f32) as usize);
+
+        let rest = stream.process_chunk(&audio[RATE..], loudness).unwrap();
+        let found = segments(&[first, rest].concat());
+        assert_eq!(found.len(), 1);
+        let (start, end) = found[0];
+        assert!(start.abs_diff(11200) <= 512 + 480, "{:?}", found);
+        assert!(end.abs_diff(25600) <= 512 + 480, "{:?}", found);
+        assert!(stream.retain_offset() >= end);
+    }
 
-        segments.sort_by_key(|s| s.start_offset);
+    #[test]
+    fn long_speech_is_cut() {
+        let found = segments(&events(
+            &mut segmenter(2.0),
+            &[speech(5.0), silence(0.5)].concat(),
+            1000,
+        ));
+
+        assert_eq!(found.len(), 3, "{:?}", found);
+        assert!(found.iter().all(|(start, end)| end - start <= 2 * RATE));
+        // The pieces follow each other without gaps
+        assert_eq!(found[0].1, found[1].0);
+    }
 
-        let mut result = Vec::new();
-        let mut left = segments[0].start_offset;
-        let mut right = segments[0].end_offset;
+    #[test]
+    fn short_sounds_get_no_start() {
+        // Longer than the minimum silence but shorter than the minimum speech
+        let audio = [silence(0.5), speech(0.2), silence(0.5)].concat();
+        assert!(events(&mut segmenter(f32::INFINITY), &audio, 512).is_empty());
 
-        for segment in segments.iter().skip(1) {
-            if segment.start_offset > right {
-                result.push(SpeechSegment::from_offsets(left, right, self.sampling_rate));
-                left = segment.start_offset;
-                right = segment.end_offset;
-            } else {
-                right = right.max(segment.end_offset);
-            }
-        }
+        // Also when the stream ends right after it
+        let audio = [silence(0.5), speech(0.2)].concat();
+        assert!(events(&mut segmenter(f32::INFINITY), &audio, 1000).is_empty());
+    }
 
-        result.push(SpeechSegment::from_offsets(left, right, self.sampling_rate));
-        result
+    #[test]
+    fn finish_closes_the_open_segment() {
+        let audio = [silence(0.5), speech(0.45)].concat();
+        let found = segments(&events(&mut segmenter(f32::INFINITY), &audio, 1000));
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].1, audio.len());
     }
 }
@@ -3,7 +3,9 @@
 // Modified for Rust implementation
 // Licensed under the MIT License
 
-#[derive(Debug, Clone)]
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
 pub struct SpeechSegment {
     pub start_offset: usize,
     pub end_offset: usize,
@@ -32,3 +34,11 @@ fn calculate_second_by_offset(offset: usize, sampling_rate: i32) -> f32 {
     let second_value = offset as f32 / sampling_rate as f32;
     (second_value * 1000.0).floor() / 1000.0
 }
+
+/// Events produced by the streaming API of the detector.
+/// Offsets are absolute sample positions from the start of the stream.
+#[derive(Debug, Clone)]
+pub enum VadEvent {
+    SpeechStart { offset: usize },
+    SpeechEnd(SpeechSegment),
+}
@@ -0,0 +1,196 @@
+use crate::protocol::{WordInfo, round_time};
+
+// Languages written without spaces; every character can be its own word
+const UNSPACED_LANGUAGES: &[&str] = &["ja", "zh", "yue", "th", "lo", "my", "km"];
+// Punctuation attached to the following word
+const LEADING_PUNCTUATION: &str = "\"'“‘¿¡([{「『（【";
+// Punctuation attached to the preceding word
+const TRAILING_PUNCTUATION: &str = "\"'”’.,!?:;)]}。、，．！？：；」』）】…";
+
+/// Text token of a Whisper segment with its absolute timing
+#[derive(Debug, Clone)]
+pub struct TimedToken {
+    // Raw bytes; a token may end in the middle of a UTF-8 character
+    pub bytes: Vec<u8>,
+    pub start: f64,
+    pub end: f64,
+    pub probability: f32,
+}
+
+/// Piece of text built from one or more tokens
+struct Piece {
+    text: String,
+    start: f64,
+    end: f64,
+    probabilities: Vec<f32>,
+}
+
+impl Piece {
+    fn append(&mut self, other: Piece) {
+        self.text.push_str(&other.text);
+        self.end = other.end;
+        self.probabilities.extend(other.probabilities);
+    }
+
+    fn is_punctuation(&self, set: &str) -> bool {
+        let text = self.text.trim();
+        !text.is_empty() && text.chars().all(|c| set.contains(c))
+    }
+}
+
+/// Merges the tokens of a segment into words.
+///
+/// Tokens are first joined until they form whole UTF-8 characters. In spaced
+/// languages a word then starts at every token with a leading space; in
+/// languages without spaces every character group is a word. Punctuation is
+/// attached to its neighbouring word. The probability of a word is the mean
+/// of its tokens.
+pub fn merge_tokens(tokens: &[TimedToken], language: &str) -> Vec<WordInfo> {
+    let unspaced = UNSPACED_LANGUAGES.contains(&language);
+
+    let mut words: Vec<Piece> = Vec::new();
+    for piece in utf8_pieces(tokens) {
+        let starts_word = unspaced || piece.text.starts_with(char::is_whitespace);
+        match words.last_mut() {
+            Some(word) if !starts_word => word.append(piece),
+            _ => words.push(piece),
+        }
+    }
+
+    let mut merged: Vec<Piece> = Vec::new();
+    let mut leading: Option<Piece> = None;
+    for word in words {
+        let word = match leading.take() {
+            Some(mut prefix) => {
+                prefix.append(word);
+                prefix
+            }
+            None => word,
+        };
+
+        if word.is_punctuation(LEADING_PUNCTUATION) {
+            leading = Some(word);
+        } else if word.is_punctuation(TRAILING_PUNCTUATION) && !merged.is_empty() {
+            merged.last_mut().unwrap().append(word);
+        } else {
+            merged.push(word);
+        }
+    }
+    merged.extend(leading);
+
+    merged
+        .into_iter()
+        .filter(|word| !word.text.trim().is_empty())
+        .map(|word| WordInfo {
+            text: word.text.trim().to_string(),
+            start: round_time(word.start),
+            end: round_time(word.end),
+            probability: word.probabilities.iter().sum::<f32>() / word.probabilities.len() as f32,
+        })
+        .collect()
+}
+
+/// Joins tokens until their bytes decode as complete characters
+fn utf8_pieces(tokens: &[TimedToken]) -> Vec<Piece> {
+    let mut pieces = Vec::new();
+    let mut bytes: Vec<u8> = Vec::new();
+    let mut start = 0.0;
+    let mut probabilities = Vec::new();
+
+    for (index, token) in tokens.iter().enumerate() {
+        if bytes.is_empty() {
+            start = token.start;
+        }
+        bytes.extend_from_slice(&token.bytes);
+        probabilities.push(token.probability);
+
+        // An incomplete character at the end waits for the next token
+        let incomplete = matches!(std::str::from_utf8(&bytes), Err(e) if e.error_len().is_none());
+        if incomplete && index + 1 < tokens.len() {
+            continue;
+        }
+
+        pieces.push(Piece {
+            text: String::from_utf8_lossy(&bytes).into_owned(),
+            start,
+            end: token.end,
+            probabilities: std::mem::take(&mut probabilities),
+        });
+        bytes.clear();
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(bytes: &[u8], start: f64, end: f64, probability: f32) -> TimedToken {
+        TimedToken {
+            bytes: bytes.to_vec(),
+            start,
+            end,
+            probability,
+        }
+    }
+
+    fn texts(words: &[WordInfo]) -> Vec<&str> {
+        words.iter().map(|word| word.text.as_str()).collect()
+    }
+
+    #[test]
+    fn spaced_language_joins_subword_tokens() {
+        let tokens = [
+            token(b" Hel", 0.0, 0.2, 0.9),
+            token(b"lo", 0.2, 0.4, 0.7),
+            token(b",", 0.4, 0.45, 0.8),
+            token(b" world", 0.5, 1.0, 0.6),
+            token(b".", 1.0, 1.05, 0.9),
+        ];
+        let words = merge_tokens(&tokens, "en");
+
+        assert_eq!(texts(&words), vec!["Hello,", "world."]);
+        assert_eq!((words[0].start, words[0].end), (0.0, 0.45));
+        assert!((words[0].probability - 0.8).abs() < 1e-6);
+        assert_eq!((words[1].start, words[1].end), (0.5, 1.05));
+    }
+
+    #[test]
+    fn unspaced_language_splits_per_character_group() {
+        let tokens = [
+            token("今日".as_bytes(), 0.0, 0.3, 0.9),
+            token("は".as_bytes(), 0.3, 0.4, 0.8),
+            token("晴れ".as_bytes(), 0.4, 0.8, 0.7),
+            token("。".as_bytes(), 0.8, 0.9, 0.9),
+        ];
+        let words = merge_tokens(&tokens, "ja");
+
+        assert_eq!(texts(&words), vec!["今日", "は", "晴れ。"]);
+        assert_eq!((words[2].start, words[2].end), (0.4, 0.9));
+    }
+
+    #[test]
+    fn characters_split_across_tokens_are_rejoined() {
+        // "語" is E8 AA 9E; Whisper may emit it as two byte-level tokens
+        let tokens = [
+            token("日本".as_bytes(), 0.0, 0.4, 0.9),
+            token(&[0xE8, 0xAA], 0.4, 0.5, 0.5),
+            token(&[0x9E], 0.5, 0.6, 0.7),
+        ];
+        let words = merge_tokens(&tokens, "ja");
+
+        assert_eq!(texts(&words), vec!["日本", "語"]);
+        assert_eq!((words[1].start, words[1].end), (0.4, 0.6));
+        assert!((words[1].probability - 0.6).abs() < 1e-6);
+    }
+
+    #[test]
+    fn opening_brackets_attach_to_the_next_word() {
+        let tokens = [
+            token("「".as_bytes(), 0.0, 0.1, 0.9),
+            token("はい".as_bytes(), 0.1, 0.5, 0.9),
+            token("」".as_bytes(), 0.5, 0.6, 0.9),
+        ];
+        assert_eq!(texts(&merge_tokens(&tokens, "ja")), vec!["「はい」"]);
+    }
+}
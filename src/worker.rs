@@ -0,0 +1,605 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tokio::sync::oneshot;
+use whisper_rs::WhisperContext;
+
+use crate::audio::PIPELINE_SAMPLE_RATE;
+use crate::config::Config;
+use crate::context::SessionContext;
+use crate::dictionary::Dictionaries;
+use crate::filter::{SegmentFilter, Verdict};
+use crate::protocol::{
+    FilteredSegment, SegmentInfo, ServerMessage, SourceTranscript, WordInfo, round_time,
+};
+use crate::transcriber::{
+    CancelToken, Segment, TranscribeOptions, Transcriber, WhisperTranscriber,
+};
+
+// How often idle workers check for shutdown
+const SHUTDOWN_POLL: Duration = Duration::from_millis(200);
+
+/// Language setting that lets Whisper detect the language of each utterance
+pub const AUTO_LANGUAGE: &str = "auto";
+
+/// What Whisper produces from the audio
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WhisperTask {
+    /// Text in the spoken language
+    #[default]
+    Transcribe,
+    /// English translation
+    Translate,
+}
+
+impl FromStr for WhisperTask {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "transcribe" => Ok(WhisperTask::Transcribe),
+            "translate" => Ok(WhisperTask::Translate),
+            _ => Err(format!(
+                "Unsupported task: {} (expected transcribe or translate)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for WhisperTask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WhisperTask::Transcribe => write!(f, "transcribe"),
+            WhisperTask::Translate => write!(f, "translate"),
+        }
+    }
+}
+
+/// Whether Whisper knows the language code, or it is `auto`
+pub fn is_supported_language(language: &str) -> bool {
+    language == AUTO_LANGUAGE || whisper_rs::get_lang_id(language).is_some()
+}
+
+/// What a task produces, which also decides its queue priority
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskKind {
+    /// A complete utterance, answered with `transcription` or `no_speech`
+    Transcription,
+    /// An interim result for an utterance that is still being spoken
+    Partial,
+    /// A complete utterance of a session that receives partial results
+    Final,
+}
+
+impl TaskKind {
+    fn label(self) -> &'static str {
+        match self {
+            TaskKind::Transcription => "utterance",
+            TaskKind::Partial => "partial",
+            TaskKind::Final => "final",
+        }
+    }
+}
+
+/// Decoding options a request may override
+#[derive(Debug, Clone, Default)]
+pub struct DecodeOptions {
+    // Static prompt, e.g. domain vocabulary
+    pub prompt: Option<String>,
+    // Tokens of recent session text added after the static prompt; 0 disables
+    pub context_tokens: usize,
+    pub context: Option<Arc<SessionContext>>,
+    pub temperature: Option<f32>,
+    // Adds per-word timing and probability to each segment
+    pub word_timestamps: bool,
+    // Languages automatic detection may choose from; empty allows all
+    pub language_candidates: Vec<String>,
+    pub task: WhisperTask,
+    // In translate mode, also transcribe in the source language (a second pass)
+    pub include_source: bool,
+    // Replacement dictionary applied to the text
+    pub dictionary: Option<String>,
+    // Adds the text before replacements to the response
+    pub include_raw: bool,
+}
+
+pub struct Task {
+    // Sequence number of the utterance within its session
+    pub seq: u64,
+    pub kind: TaskKind,
+    pub audio_data: Vec<f32>,
+    // Language code, or `auto` to detect it
+    pub language: String,
+    // Absolute start of the utterance within the session, in seconds
+    pub start_time: f64,
+    pub options: DecodeOptions,
+    pub responder: oneshot::Sender<ServerMessage>,
+    // Set once the result is no longer wanted
+    pub cancel: CancelToken,
+}
+
+/// Pending tasks; partials only run when no complete utterance is waiting
+#[derive(Default)]
+struct PendingTasks {
+    utterances: VecDeque<Task>,
+    partials: VecDeque<Task>,
+}
+
+impl PendingTasks {
+    fn push(&mut self, task: Task) {
+        match task.kind {
+            TaskKind::Partial => self.partials.push_back(task),
+            TaskKind::Transcription | TaskKind::Final => self.utterances.push_back(task),
+        }
+    }
+
+    fn pop(&mut self) -> Option<Task> {
+        self.utterances
+            .pop_front()
+            .or_else(|| self.partials.pop_front())
+    }
+
+    fn len(&self) -> usize {
+        self.utterances.len() + self.partials.len()
+    }
+}
+
+struct TaskQueue {
+    tasks: Mutex<PendingTasks>,
+    available: Condvar,
+}
+
+/// A fixed set of worker threads, each owning one transcriber
+pub struct WorkerPool {
+    queue: Arc<TaskQueue>,
+    dictionaries: Arc<Dictionaries>,
+}
+
+/// Loads the replacement dictionaries, checking that the default one exists
+pub fn load_dictionaries(config: &Config) -> Result<Dictionaries, String> {
+    let dictionaries = Dictionaries::load(config.dictionary_dir.as_deref())?;
+    if let Some(name) = &config.dictionary
+        && !dictionaries.contains(name)
+    {
+        return Err(format!("Unknown dictionary: {}", name));
+    }
+    Ok(dictionaries)
+}
+
+impl WorkerPool {
+    /// Creates one Whisper state per worker up front and starts the worker threads.
+    /// `whisper_threads` is split evenly between the workers.
+    pub fn whisper(
+        ctx: Arc<WhisperContext>,
+        config: &Config,
+        shutdown: Arc<AtomicBool>,
+    ) -> Result<Self, String> {
+        let workers = config.whisper_workers.max(1);
+        let threads_per_worker = (config.whisper_threads / workers).max(1);
+        let transcribers = (0..workers)
+            .map(|id| {
+                WhisperTranscriber::new(ctx.clone(), threads_per_worker)
+                    .map(|transcriber| Box::new(transcriber) as Box<dyn Transcriber>)
+                    .map_err(|e| format!("Failed to create state for worker {}: {}", id, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let pool = Self::new(transcribers, config, shutdown)?;
+        println!(
+            "👷 Started {} Whisper worker(s) with {} thread(s) each",
+            workers, threads_per_worker
+        );
+        Ok(pool)
+    }
+
+    /// Starts one worker thread per transcriber
+    pub fn new(
+        transcribers: Vec<Box<dyn Transcriber>>,
+        config: &Config,
+        shutdown: Arc<AtomicBool>,
+    ) -> Result<Self, String> {
+        let queue = Arc::new(TaskQueue {
+            tasks: Mutex::new(PendingTasks::default()),
+            available: Condvar::new(),
+        });
+
+        let filter = SegmentFilter::from_config(config)?;
+
+        let dictionaries = Arc::new(load_dictionaries(config)?);
+        dictionaries.clone().watch(shutdown.clone());
+
+        for (id, transcriber) in transcribers.into_iter().enumerate() {
+            let worker = Worker {
+                id,
+                transcriber,
+                filter: filter.clone(),
+                dictionaries: dictionaries.clone(),
+                queue: queue.clone(),
+                shutdown: shutdown.clone(),
+                busy: Duration::ZERO,
+            };
+
+            thread::Builder::new()
+                .name(format!("transcribe-worker-{}", id))
+                .spawn(move || worker.run())
+                .map_err(|e| format!("Failed to start worker {}: {}", id, e))?;
+        }
+
+        Ok(Self {
+            queue,
+            dictionaries,
+        })
+    }
+
+    /// Replacement dictionaries sessions can choose from
+    pub fn dictionaries(&self) -> &Dictionaries {
+        &self.dictionaries
+    }
+
+    /// Queues a task and returns the queue depth after adding it
+    pub fn submit(&self, task: Task) -> usize {
+        let depth = {
+            let mut tasks = self.queue.tasks.lock().unwrap();
+            tasks.push(task);
+            tasks.len()
+        };
+        self.queue.available.notify_one();
+        depth
+    }
+}
+
+struct Worker {
+    id: usize,
+    transcriber: Box<dyn Transcriber>,
+    filter: SegmentFilter,
+    dictionaries: Arc<Dictionaries>,
+    queue: Arc<TaskQueue>,
+    shutdown: Arc<AtomicBool>,
+    // Total time spent transcribing
+    busy: Duration,
+}
+
+impl Worker {
+    /// Waits for the next task and the number still queued after it; None on shutdown
+    fn next_task(&self) -> Option<(Task, usize)> {
+        let mut tasks = self.queue.tasks.lock().unwrap();
+        loop {
+            if self.shutdown.load(Ordering::SeqCst) {
+                return None;
+            }
+            if let Some(task) = tasks.pop() {
+                return Some((task, tasks.len()));
+            }
+            tasks = self
+                .queue
+                .available
+                .wait_timeout(tasks, SHUTDOWN_POLL)
+                .unwrap()
+                .0;
+        }
+    }
+
+    /// Answers the tasks nobody will transcribe any more
+    fn reject_queued(&self) {
+        let mut tasks = self.queue.tasks.lock().unwrap();
+        while let Some(task) = tasks.pop() {
+            let _ = task.responder.send(ServerMessage::Error {
+                seq: Some(task.seq),
+                message: "Server is shutting down".to_string(),
+            });
+        }
+    }
+
+    fn run(mut self) {
+        loop {
+            let Some((task, depth)) = self.next_task() else {
+                println!("Worker {} shutting down...", self.id);
+                self.reject_queued();
+                break;
+            };
+
+            if task.cancel.is_cancelled() {
+                println!(
+                    "⏭️  Worker {} skipping cancelled {} #{}",
+                    self.id,
+                    task.kind.label(),
+                    task.seq
+                );
+                continue;
+            }
+
+            let duration = task.audio_data.len() as f64 / PIPELINE_SAMPLE_RATE as f64;
+            println!(
+                "🎤 Worker {} transcribing {} #{} ({} samples, {:.2}s, {} still queued)...",
+                self.id,
+                task.kind.label(),
+                task.seq,
+                task.audio_data.len(),
+                duration,
+                depth
+            );
+
+            let started = Instant::now();
+            let message = self.transcribe(&task, duration);
+            let elapsed = started.elapsed();
+            self.busy += elapsed;
+
+            if task.cancel.is_cancelled() {
+                println!(
+                    "⏹️  Worker {} aborted cancelled {} #{} after {:.2}s",
+                    self.id,
+                    task.kind.label(),
+                    task.seq,
+                    elapsed.as_secs_f64()
+                );
+                continue;
+            }
+
+            println!(
+                "✅ Worker {} finished in {:.2}s (busy {:.1}s total)",
+                self.id,
+                elapsed.as_secs_f64(),
+                self.busy.as_secs_f64()
+            );
+
+            // Later utterances of the session use this text as their prompt
+            if let (
+                Some(context),
+                ServerMessage::Transcription { transcription, .. }
+                | ServerMessage::Final { transcription, .. },
+            ) = (&task.options.context, &message)
+            {
+                context.record(task.seq, transcription);
+            }
+
+            // Send result back
+            let _ = task.responder.send(message);
+        }
+    }
+
+    fn transcribe(&mut self, task: &Task, duration: f64) -> ServerMessage {
+        let start_time = task.start_time;
+
+        let detected = if task.language == AUTO_LANGUAGE {
+            match self
+                .transcriber
+                .detect_language(&task.audio_data, &task.options.language_candidates)
+            {
+                Ok(detected) => {
+                    println!(
+                        "🌐 Worker {} detected language {} ({:.2}) for {} #{}",
+                        self.id,
+                        detected.language,
+                        detected.language_probability,
+                        task.kind.label(),
+                        task.seq
+                    );
+                    Some(detected)
+                }
+                Err(e) => {
+                    return ServerMessage::Error {
+                        seq: Some(task.seq),
+                        message: format!("Language detection failed: {}", e),
+                    };
+                }
+            }
+        } else {
+            None
+        };
+        let language = detected
+            .as_ref()
+            .map_or(task.language.as_str(), |d| d.language.as_str());
+
+        let context = match &task.options.context {
+            Some(context) if task.options.context_tokens > 0 => context.before(task.seq),
+            _ => String::new(),
+        };
+        // Partials carry no segments, so they skip the extra work
+        let word_timestamps = task.options.word_timestamps && task.kind != TaskKind::Partial;
+        let translate = task.options.task == WhisperTask::Translate;
+        let mut options = TranscribeOptions {
+            language,
+            prompt: task.options.prompt.as_deref(),
+            context: &context,
+            context_tokens: task.options.context_tokens,
+            translate: false,
+            temperature: task.options.temperature,
+            word_timestamps,
+            cancel: &task.cancel,
+        };
+
+        // The source-language transcript is a separate pass before the translation
+        let source = if translate && task.options.include_source && task.kind != TaskKind::Partial {
+            let segments = match self.transcriber.transcribe(&task.audio_data, &options) {
+                Ok(segments) => segments,
+                Err(message) => {
+                    return ServerMessage::Error {
+                        seq: Some(task.seq),
+                        message,
+                    };
+                }
+            };
+            let source = self.collect_segments(task, segments, language, word_timestamps);
+            Some(Box::new(SourceTranscript {
+                transcription: source.transcription,
+                raw_transcription: source.raw_transcription,
+                segments: source.segments,
+            }))
+        } else {
+            None
+        };
+
+        options.translate = translate;
+        let segments = match self.transcriber.transcribe(&task.audio_data, &options) {
+            Ok(segments) => segments,
+            Err(message) => {
+                return ServerMessage::Error {
+                    seq: Some(task.seq),
+                    message,
+                };
+            }
+        };
+        // Translations are always English
+        let output_language = if translate { "en" } else { language };
+        let Collected {
+            transcription,
+            raw_transcription,
+            segments,
+            filtered,
+        } = self.collect_segments(task, segments, output_language, word_timestamps);
+        let whisper_task = translate.then_some(WhisperTask::Translate);
+        let seq = task.seq;
+        let (duration, start, end) = (
+            round_time(duration),
+            round_time(start_time),
+            round_time(start_time + duration),
+        );
+        match task.kind {
+            TaskKind::Partial => ServerMessage::Partial {
+                seq,
+                transcription,
+                raw_transcription,
+                task: whisper_task,
+                detected_language: detected,
+                duration,
+                start,
+                end,
+            },
+            TaskKind::Final => ServerMessage::Final {
+                seq,
+                transcription,
+                raw_transcription,
+                task: whisper_task,
+                source,
+                detected_language: detected,
+                segments,
+                filtered,
+                duration,
+                start,
+                end,
+            },
+            TaskKind::Transcription if transcription.is_empty() => ServerMessage::NoSpeech {
+                seq,
+                filtered,
+                duration,
+                start,
+                end,
+            },
+            TaskKind::Transcription => ServerMessage::Transcription {
+                seq,
+                transcription,
+                raw_transcription,
+                task: whisper_task,
+                source,
+                detected_language: detected,
+                segments,
+                filtered,
+                duration,
+                start,
+                end,
+            },
+        }
+    }
+
+    /// Text and segments of a run, with times relative to the session start.
+    /// Segments pass the NG word and hallucination filters, then the task's dictionary.
+    fn collect_segments(
+        &self,
+        task: &Task,
+        segments: Vec<Segment>,
+        language: &str,
+        word_timestamps: bool,
+    ) -> Collected {
+        let start_time = task.start_time;
+        let dictionary = task
+            .options
+            .dictionary
+            .as_deref()
+            .and_then(|name| self.dictionaries.get(name));
+        let mut collected = Collected::default();
+        let mut raw_transcription = String::new();
+
+        for segment in segments {
+            let trimmed_text = segment.text.trim();
+
+            let start = round_time(start_time + segment.start);
+            let end = round_time(start_time + segment.end);
+
+            let (text, flag) = match self.filter.apply(trimmed_text, &segment.stats) {
+                Verdict::Keep { text, flag } => (text, flag),
+                Verdict::Drop(reason) => {
+                    println!(
+                        "🚫 Worker {} dropped segment ({:?}): {}",
+                        self.id, reason, trimmed_text
+                    );
+                    collected.filtered.push(FilteredSegment {
+                        start,
+                        end,
+                        text: trimmed_text.to_string(),
+                        reason,
+                    });
+                    continue;
+                }
+            };
+
+            let corrected = match &dictionary {
+                Some(dictionary) => dictionary.apply(&text, language).into_owned(),
+                None => text.clone(),
+            };
+
+            // Words of a segment changed by NG rules would reveal the deleted or masked text,
+            // and those of a corrected one would not match its text
+            let words = (word_timestamps && text == trimmed_text && corrected == text).then(|| {
+                segment
+                    .words
+                    .iter()
+                    .map(|word| WordInfo {
+                        start: round_time(start_time + word.start),
+                        end: round_time(start_time + word.end),
+                        ..word.clone()
+                    })
+                    .collect()
+            });
+
+            collected.transcription.push_str(&corrected);
+            collected.transcription.push(' ');
+            raw_transcription.push_str(&text);
+            raw_transcription.push(' ');
+
+            collected.segments.push(SegmentInfo {
+                start,
+                end,
+                text: corrected,
+                raw_text: task.options.include_raw.then_some(text),
+                words,
+                filter: flag,
+            });
+        }
+
+        collected.transcription = collected.transcription.trim().to_string();
+        collected.raw_transcription = task
+            .options
+            .include_raw
+            .then(|| raw_transcription.trim().to_string());
+        collected
+    }
+}
+
+/// Text and segments of one transcription run
+#[derive(Debug, Default)]
+struct Collected {
+    transcription: String,
+    // Text before dictionary replacements, when requested
+    raw_transcription: Option<String>,
+    segments: Vec<SegmentInfo>,
+    // Segments removed by the NG word and hallucination filters
+    filtered: Vec<FilteredSegment>,
+}
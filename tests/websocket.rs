@@ -0,0 +1,198 @@
+//! End-to-end WebSocket sessions with a scripted transcriber instead of Whisper
+//! and the energy VAD, so no model is needed.
+
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use serde_json::{Value, json};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async};
+use whisper_server_ws::vad::{VadBackend, VadFactory};
+use whisper_server_ws::{Config, ScriptedTranscriber, Transcriber, WorkerPool, server};
+
+type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+const RATE: usize = 16000;
+
+/// Starts a server on a free port and connects a client to it
+async fn connect(transcriber: &ScriptedTranscriber) -> Client {
+    let config = Config {
+        vad_backend: VadBackend::Energy,
+        ..Config::default()
+    };
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let transcribers: Vec<Box<dyn Transcriber>> = vec![Box::new(transcriber.clone())];
+    let pool = Arc::new(WorkerPool::new(transcribers, &config, shutdown.clone()).unwrap());
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}", listener.local_addr().unwrap());
+    let vad = VadFactory::new(&config).unwrap();
+    tokio::spawn(server::serve_websocket(
+        listener, config, pool, vad, shutdown,
+    ));
+
+    let (client, _) = connect_async(url).await.unwrap();
+    client
+}
+
+/// A tone the energy VAD takes for speech, followed by silence, as f32le frames
+async fn speak(client: &mut Client, seconds: f32, pause: f32) {
+    let tone = (0..(seconds * RATE as f32) as usize)
+        .map(|i| 0.3 * (i as f32 * 220.0 * std::f32::consts::TAU / RATE as f32).sin());
+    let silence = std::iter::repeat_n(0.0f32, (pause * RATE as f32) as usize);
+    let samples: Vec<f32> = tone.chain(silence).collect();
+
+    // 100 ms frames, as a client streaming live would send them
+    for frame in samples.chunks(RATE / 10) {
+        let bytes: Vec<u8> = frame.iter().flat_map(|s| s.to_le_bytes()).collect();
+        client.send(Message::Binary(bytes.into())).await.unwrap();
+    }
+}
+
+async fn send(client: &mut Client, message: Value) {
+    client
+        .send(Message::Text(message.to_string().into()))
+        .await
+        .unwrap();
+}
+
+/// Next server message, or None once the server closed the session
+async fn receive(client: &mut Client) -> Option<Value> {
+    match client.next().await? {
+        Ok(Message::Text(text)) => Some(serde_json::from_str(&text).unwrap()),
+        Ok(Message::Close(_)) => None,
+        frame => panic!("unexpected frame: {:?}", frame),
+    }
+}
+
+#[tokio::test]
+async fn session_starts_ready_and_applies_config() {
+    let mut client = connect(&ScriptedTranscriber::new()).await;
+
+    let ready = receive(&mut client).await.unwrap();
+    assert_eq!(ready["type"], "status");
+    assert_eq!(ready["status"], "ready");
+
+    send(&mut client, json!({"type": "config", "language": "en"})).await;
+    let applied = receive(&mut client).await.unwrap();
+    assert_eq!(applied["status"], "config_applied");
+    assert_eq!(applied["config"]["language"], "en");
+
+    send(&mut client, json!({"type": "config", "language": "xx"})).await;
+    assert_eq!(receive(&mut client).await.unwrap()["type"], "error");
+}
+
+#[tokio::test]
+async fn unknown_text_frame_is_an_error() {
+    let mut client = connect(&ScriptedTranscriber::new()).await;
+    receive(&mut client).await.unwrap();
+
+    client.send(Message::Text("hello".into())).await.unwrap();
+    assert_eq!(receive(&mut client).await.unwrap()["type"], "error");
+}
+
+#[tokio::test]
+async fn rejected_json_names_the_field() {
+    let mut client = connect(&ScriptedTranscriber::new()).await;
+    receive(&mut client).await.unwrap();
+
+    send(&mut client, json!({"type": "config", "vad_treshold": 0.6})).await;
+    let error = receive(&mut client).await.unwrap();
+    assert_eq!(error["type"], "error");
+    assert!(
+        error["message"].as_str().unwrap().contains("vad_treshold"),
+        "{}",
+        error
+    );
+
+    send(&mut client, json!({"type": "config", "language": 5})).await;
+    let error = receive(&mut client).await.unwrap();
+    assert!(
+        error["message"].as_str().unwrap().contains("invalid type"),
+        "{}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn close_exports_the_transcript_then_closes() {
+    let mut client = connect(&ScriptedTranscriber::new()).await;
+    receive(&mut client).await.unwrap();
+
+    send(&mut client, json!({"type": "close", "format": "vtt"})).await;
+    let subtitle = receive(&mut client).await.unwrap();
+    assert_eq!(subtitle["type"], "subtitle");
+    assert!(subtitle["content"].as_str().unwrap().starts_with("WEBVTT"));
+    assert!(receive(&mut client).await.is_none());
+}
+
+#[tokio::test]
+async fn utterances_are_answered_in_order() {
+    // Both utterances are queued before the first answer is ready
+    let transcriber = ScriptedTranscriber::new().with_delay(Duration::from_millis(100));
+    transcriber.respond(&["first utterance"]);
+    transcriber.respond(&["second utterance"]);
+    let mut client = connect(&transcriber).await;
+    receive(&mut client).await.unwrap();
+
+    speak(&mut client, 1.0, 0.5).await;
+    speak(&mut client, 0.8, 0.5).await;
+
+    let first = receive(&mut client).await.unwrap();
+    assert_eq!(first["type"], "transcription");
+    assert_eq!(first["seq"], 0);
+    assert_eq!(first["transcription"], "first utterance");
+    let start = first["start"].as_f64().unwrap();
+    assert!(start < 0.1, "{}", first);
+
+    let second = receive(&mut client).await.unwrap();
+    assert_eq!(second["seq"], 1);
+    assert_eq!(second["transcription"], "second utterance");
+    assert!(second["start"].as_f64().unwrap() > 1.4, "{}", second);
+
+    send(&mut client, json!({"type": "close", "format": "srt"})).await;
+    let subtitle = receive(&mut client).await.unwrap();
+    let content = subtitle["content"].as_str().unwrap();
+    assert!(content.contains("first utterance") && content.contains("second utterance"));
+    assert!(receive(&mut client).await.is_none());
+}
+
+#[tokio::test]
+async fn flush_ends_the_open_utterance() {
+    let transcriber = ScriptedTranscriber::new();
+    transcriber.respond(&["still talking"]);
+    let mut client = connect(&transcriber).await;
+    receive(&mut client).await.unwrap();
+
+    speak(&mut client, 1.0, 0.0).await;
+    client.send(Message::Text("flush".into())).await.unwrap();
+
+    let message = receive(&mut client).await.unwrap();
+    assert_eq!(message["transcription"], "still talking");
+}
+
+#[tokio::test]
+async fn session_settings_reach_the_transcriber() {
+    let transcriber = ScriptedTranscriber::new();
+    let mut client = connect(&transcriber).await;
+    receive(&mut client).await.unwrap();
+
+    send(
+        &mut client,
+        json!({"type": "config", "language": "en", "prompt": "Rust, WebSocket"}),
+    )
+    .await;
+    receive(&mut client).await.unwrap();
+    speak(&mut client, 1.0, 0.5).await;
+
+    // Nothing was scripted, so the utterance has no speech
+    assert_eq!(receive(&mut client).await.unwrap()["type"], "no_speech");
+    let calls = transcriber.calls();
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].language, "en");
+    assert_eq!(calls[0].prompt.as_deref(), Some("Rust, WebSocket"));
+    // Roughly the second of speech, with the padding
+    assert!((15000..18000).contains(&calls[0].samples), "{:?}", calls);
+}
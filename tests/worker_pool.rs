@@ -0,0 +1,218 @@
+//! Worker pool behaviour with a scripted transcriber; runs without any model.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use tokio::sync::oneshot;
+use whisper_server_ws::context::SessionContext;
+use whisper_server_ws::transcriber::CancelToken;
+use whisper_server_ws::worker::{DecodeOptions, Task, TaskKind, WhisperTask};
+use whisper_server_ws::{Config, ScriptedTranscriber, ServerMessage, Transcriber, WorkerPool};
+
+fn pool(transcriber: &ScriptedTranscriber, config: &Config) -> WorkerPool {
+    let transcribers: Vec<Box<dyn Transcriber>> = vec![Box::new(transcriber.clone())];
+    WorkerPool::new(transcribers, config, Arc::new(AtomicBool::new(false))).unwrap()
+}
+
+fn task(
+    seq: u64,
+    language: &str,
+    options: DecodeOptions,
+) -> (Task, oneshot::Receiver<ServerMessage>) {
+    let (responder, result) = oneshot::channel();
+    let task = Task {
+        seq,
+        kind: TaskKind::Transcription,
+        // One second starting at 2s into the session
+        audio_data: vec![0.0; 16000],
+        language: language.to_string(),
+        start_time: 2.0,
+        options,
+        responder,
+        cancel: CancelToken::default(),
+    };
+    (task, result)
+}
+
+#[tokio::test]
+async fn segments_are_filtered_and_placed_in_the_session() {
+    let transcriber = ScriptedTranscriber::new();
+    transcriber.respond(&["Hello there.", "ご視聴ありがとうございました"]);
+    let pool = pool(&transcriber, &Config::default());
+
+    let options = DecodeOptions {
+        word_timestamps: true,
+        ..DecodeOptions::default()
+    };
+    let (task, result) = task(0, "ja", options);
+    pool.submit(task);
+
+    match result.await.unwrap() {
+        ServerMessage::Transcription {
+            transcription,
+            segments,
+            filtered,
+            start,
+            end,
+            ..
+        } => {
+            assert_eq!(transcription, "Hello there.");
+            assert_eq!((start, end), (2.0, 3.0));
+            assert_eq!((segments[0].start, segments[0].end), (2.0, 2.5));
+            let words = segments[0].words.as_ref().unwrap();
+            assert_eq!(words[1].text, "there.");
+            assert_eq!((words[1].start, words[1].end), (2.25, 2.5));
+            assert_eq!(filtered.len(), 1);
+            assert_eq!(filtered[0].text, "ご視聴ありがとうございました");
+        }
+        message => panic!("unexpected message: {:?}", message),
+    }
+}
+
+#[tokio::test]
+async fn corrected_segments_have_no_words() {
+    let dir = std::env::temp_dir().join(format!("worker-dictionary-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("names.csv"), "there,everyone\n").unwrap();
+    let config = Config {
+        dictionary_dir: dir.to_str().map(str::to_string),
+        ..Config::default()
+    };
+
+    let transcriber = ScriptedTranscriber::new();
+    transcriber.respond(&["Hello there.", "Good morning."]);
+    let pool = pool(&transcriber, &config);
+
+    let options = DecodeOptions {
+        word_timestamps: true,
+        dictionary: Some("names".to_string()),
+        ..DecodeOptions::default()
+    };
+    let (task, result) = task(0, "en", options);
+    pool.submit(task);
+
+    match result.await.unwrap() {
+        ServerMessage::Transcription { segments, .. } => {
+            assert_eq!(segments[0].text, "Hello everyone.");
+            assert!(segments[0].words.is_none());
+            assert!(segments[1].words.is_some());
+        }
+        message => panic!("unexpected message: {:?}", message),
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn empty_response_is_no_speech() {
+    let transcriber = ScriptedTranscriber::new();
+    let pool = pool(&transcriber, &Config::default());
+
+    let (task, result) = task(0, "en", DecodeOptions::default());
+    pool.submit(task);
+
+    assert!(matches!(
+        result.await.unwrap(),
+        ServerMessage::NoSpeech { seq: 0, .. }
+    ));
+}
+
+#[tokio::test]
+async fn earlier_text_is_passed_as_context() {
+    let transcriber = ScriptedTranscriber::new();
+    transcriber.respond(&["first"]);
+    transcriber.respond(&["second"]);
+    let pool = pool(&transcriber, &Config::default());
+    let context = Arc::new(SessionContext::default());
+
+    for seq in 0..2 {
+        let options = DecodeOptions {
+            prompt: Some("glossary".to_string()),
+            context_tokens: 16,
+            context: Some(context.clone()),
+            ..DecodeOptions::default()
+        };
+        let (task, result) = task(seq, "en", options);
+        pool.submit(task);
+        result.await.unwrap();
+    }
+
+    let calls = transcriber.calls();
+    assert_eq!(calls[0].context, "");
+    assert_eq!(calls[1].context, "first");
+    assert_eq!(calls[1].prompt.as_deref(), Some("glossary"));
+}
+
+#[tokio::test]
+async fn translation_with_source_runs_twice() {
+    let transcriber = ScriptedTranscriber::new().with_language("de");
+    transcriber.respond(&["Guten Morgen"]);
+    transcriber.respond(&["Good morning"]);
+    let pool = pool(&transcriber, &Config::default());
+
+    let options = DecodeOptions {
+        task: WhisperTask::Translate,
+        include_source: true,
+        ..DecodeOptions::default()
+    };
+    let (task, result) = task(0, "auto", options);
+    pool.submit(task);
+
+    match result.await.unwrap() {
+        ServerMessage::Transcription {
+            transcription,
+            source,
+            detected_language,
+            ..
+        } => {
+            assert_eq!(transcription, "Good morning");
+            assert_eq!(source.unwrap().transcription, "Guten Morgen");
+            assert_eq!(detected_language.unwrap().language, "de");
+        }
+        message => panic!("unexpected message: {:?}", message),
+    }
+    let translate: Vec<bool> = transcriber.calls().iter().map(|c| c.translate).collect();
+    assert_eq!(translate, vec![false, true]);
+}
+
+#[tokio::test]
+async fn cancelled_task_gets_no_answer() {
+    let transcriber = ScriptedTranscriber::new().with_delay(Duration::from_secs(60));
+    transcriber.respond(&["never delivered"]);
+    let pool = pool(&transcriber, &Config::default());
+
+    let (task, result) = task(0, "en", DecodeOptions::default());
+    let cancel = task.cancel.clone();
+    pool.submit(task);
+    cancel.cancel();
+
+    assert!(result.await.is_err());
+}
+
+#[tokio::test]
+async fn queued_tasks_are_rejected_on_shutdown() {
+    let transcriber = ScriptedTranscriber::new().with_delay(Duration::from_millis(100));
+    transcriber.respond(&["in progress"]);
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let transcribers: Vec<Box<dyn Transcriber>> = vec![Box::new(transcriber.clone())];
+    let pool = WorkerPool::new(transcribers, &Config::default(), shutdown.clone()).unwrap();
+
+    let (running, running_result) = task(0, "en", DecodeOptions::default());
+    pool.submit(running);
+    while transcriber.calls().is_empty() {
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    let (queued, queued_result) = task(1, "en", DecodeOptions::default());
+    pool.submit(queued);
+    shutdown.store(true, Ordering::SeqCst);
+
+    // The running task still finishes; the queued one is answered, not dropped
+    assert!(matches!(
+        running_result.await.unwrap(),
+        ServerMessage::Transcription { seq: 0, .. }
+    ));
+    assert!(matches!(
+        queued_result.await.unwrap(),
+        ServerMessage::Error { seq: Some(1), .. }
+    ));
+}